@@ -1,30 +1,39 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    to_binary, Addr, Binary, Deps, DepsMut, Env,
-    MessageInfo, QuerierWrapper, Response, StdError, StdResult, Uint128, WasmMsg, Storage, Order, Api, attr, QueryRequest, BankQuery, CosmosMsg, WasmQuery, Coin,
-    BalanceResponse, SubMsg
+    to_binary, to_json_binary, Addr, Binary, Deps, DepsMut, Env,
+    MessageInfo, QuerierWrapper, Response, StdError, StdResult, Uint128, Storage, Order, Api, attr, CosmosMsg,
+    SubMsg, Reply, BlockInfo
 };
 
-use wasmswap::msg::{InfoResponse, ExecuteMsg as WasmswapExecuteMsg, QueryMsg as WasmswapQueryMsg, TokenSelect};
 
-use cw2::set_contract_version;
+use std::cmp::Ordering;
+
+use cw2::{get_contract_version, set_contract_version};
+use cw_storage_plus::Bound;
+use cw_utils::Duration;
+use semver::Version;
 use cw20_base::allowances::{
     execute_burn_from, execute_decrease_allowance, execute_increase_allowance, execute_send_from,
     execute_transfer_from, query_allowance, deduct_allowance
 };
 
 use cw20_base::state::{MinterData, TokenInfo, TOKEN_INFO};
-use cw20::{TokenInfoResponse, BalanceResponse as CW20BalanceResponse, Cw20ReceiveMsg, Cw20QueryMsg, Cw20ExecuteMsg};
+use cw20::{Denom, Expiration, TokenInfoResponse, BalanceResponse as CW20BalanceResponse, Cw20ReceiveMsg};
 use crate::error::ContractError;
-use crate::msg::{ConfigResponse, ExecuteMsg, InstantiateMsg, QueryMsg, ExcludedInfo, BoolResponse, Uint128Response};
-use crate::state::{Config, CONFIG, ROWNED, TOWNED, ISEXCLUDEDFROMFEE, ISEXCLUDED};
+use crate::migrations::migrate_config_v0_12;
+use crate::dex::{denom_transfer_msg, query_denom_balance, Dex};
+use crate::msg::{ConfigResponse, ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg, BoolResponse, OwnershipProposalResponse, RolesResponse, PendingChangesResponse, AmmPairInfo, AmmPairsResponse, WithdrawalsResponse, Uint128Response};
+use crate::state::{Config, CONFIG, BPS_DENOMINATOR, OwnershipProposal, OWNERSHIP_PROPOSAL, Role, ROLES, FeeProfile, TransferKind, AmmPair, AMM_PAIRS, ConfigChange, PendingChange, PENDING_CHANGES, PENDING_CHANGE_SEQ, PendingLiquify, PENDING_LIQUIFY, PendingLiquidity, PENDING_LIQUIDITY, LpPolicy, Withdrawal, WithdrawCaps, WITHDRAWALS, WITHDRAWAL_SEQ, NEXT_LIQUIFY, ROWNED, TOWNED, ISEXCLUDEDFROMFEE, ISEXCLUDED};
 
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:cw20-safemoon";
 const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+pub const LIQUIFY_SWAP_REPLY_ID: u64 = 1;
+pub const LIQUIFY_PROVIDE_REPLY_ID: u64 = 2;
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
     deps: DepsMut,
@@ -33,6 +42,23 @@ pub fn instantiate(
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
     set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    if msg.max_total_fee > BPS_DENOMINATOR {
+        return Err(ContractError::FeeTooHigh {});
+    }
+    _check_fees(msg.max_total_fee, &msg.buy_fee, msg.marketing_fee)?;
+    _check_fees(msg.max_total_fee, &msg.sell_fee, msg.marketing_fee)?;
+    _check_fees(msg.max_total_fee, &msg.transfer_fee, msg.marketing_fee)?;
+    if msg.max_slippage_bps > BPS_DENOMINATOR {
+        return Err(ContractError::SlippageTooHigh {});
+    }
+    let marketing_address = msg.marketing_address
+        .map(|address| deps.api.addr_validate(&address))
+        .transpose()?;
+    if let Denom::Cw20(token) = &msg.denom {
+        deps.api.addr_validate(token.as_str())?;
+    }
+    _validate_lp_policy(deps.api, &msg.lp_policy)?;
     
     // multiple values for every size value
     let multiply = Uint128::from(1u128);
@@ -46,24 +72,41 @@ pub fn instantiate(
     ISEXCLUDEDFROMFEE.save(deps.storage, env.contract.address.clone(), &true)?;
 
     let config = Config {
-        owner: info.clone().sender.clone(),
+        owner: Some(info.clone().sender.clone()),
         t_total,
         r_total,
         t_fee_total: Uint128::zero(),
-        tax_fee: msg.tax_fee,
-        previous_tax_fee: msg.tax_fee,
-        liquidity_fee: msg.liquidity_fee,
-        previous_liquidity_fee: msg.liquidity_fee,
-        pool_address: msg.pool_address,
+        t_burn_total: Uint128::zero(),
+        excluded_r_total: Uint128::zero(),
+        excluded_t_total: Uint128::zero(),
+        buy_fee: msg.buy_fee,
+        sell_fee: msg.sell_fee,
+        marketing_fee: msg.marketing_fee,
+        marketing_address,
+        marketing_tokens: Uint128::zero(),
+        transfer_fee: msg.transfer_fee,
+        max_total_fee: msg.max_total_fee,
+        pool_address: msg.pool_address.clone(),
+        // the pool is usually created after the token, see UpdatePool
+        denom_side: None,
         swap_and_liquidity_enabled: true,
         // set max_tx_amount to 100k
         max_tx_amount: multiply * Uint128::from(100u128 * 1000u128 * 10u128.pow(msg.decimals as u32)),
         // set num_tokens_sell_to_add_to_liquidity to 5K
         num_tokens_sell_to_add_to_liquidity: multiply * Uint128::from(5u128 * 1000u128 * 10u128.pow(msg.decimals as u32)),
-        denom: msg.denom
+        // set max_liquify_amount to 100k
+        max_liquify_amount: multiply * Uint128::from(100u128 * 1000u128 * 10u128.pow(msg.decimals as u32)),
+        denom: msg.denom,
+        timelock: msg.timelock,
+        liquify_interval: msg.liquify_interval,
+        max_slippage_bps: msg.max_slippage_bps,
+        swap_expiration: msg.swap_expiration,
+        lp_policy: msg.lp_policy,
+        withdraw_caps: msg.withdraw_caps
     };
     
     CONFIG.save(deps.storage, &config)?;
+    AMM_PAIRS.save(deps.storage, msg.pool_address, &AmmPair { dex: msg.dex, buy_fee: None, sell_fee: None })?;
 
     // store token info using cw20-base format
     let data = TokenInfo {
@@ -93,8 +136,17 @@ pub fn execute(
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::UpdateConfig { new_owner } => update_config(deps, info, new_owner),
-        ExecuteMsg::UpdatePool { address } => update_pool(deps, info, address),
+        ExecuteMsg::ProposeNewOwner { owner, expiry } => execute_propose_new_owner(deps, env, info, owner, expiry),
+        ExecuteMsg::AcceptOwnership {} => execute_accept_ownership(deps, env, info),
+        ExecuteMsg::CancelOwnershipProposal {} => execute_cancel_ownership_proposal(deps, info),
+        ExecuteMsg::RenounceOwnership {} => execute_renounce_ownership(deps, info),
+        ExecuteMsg::GrantRole { address, role } => execute_grant_role(deps, info, address, role),
+        ExecuteMsg::RevokeRole { address, role } => execute_revoke_role(deps, info, address, role),
+        ExecuteMsg::UpdatePool { address, dex } => update_pool(deps, env, info, address, dex),
+        ExecuteMsg::AddAmmPair { address, dex, buy_fee, sell_fee } => execute_add_amm_pair(deps, env, info, address, dex, buy_fee, sell_fee),
+        ExecuteMsg::RemoveAmmPair { address } => execute_remove_amm_pair(deps, env, info, address),
+        ExecuteMsg::ExecutePendingChange { id } => execute_pending_change(deps, env, info, id),
+        ExecuteMsg::CancelPendingChange { id } => execute_cancel_pending_change(deps, info, id),
         // these all come from cw20-base to implement the cw20 standard
         ExecuteMsg::Transfer { recipient, amount } => {
             execute_custom_transfer(deps, env, info, recipient, amount)
@@ -149,83 +201,195 @@ pub fn execute(
         ExecuteMsg::IncludeInFee {
             address
         } => execute_include_in_fee(deps, env, info, address),
-        ExecuteMsg::SetTaxFeePercent {
-            percent
-        } => execute_set_tax_fee_percent(deps, env, info, percent),
-        ExecuteMsg::SetLiquidityFeePercent {
-            percent
-        } => execute_set_liquidity_fee_percent(deps, env, info, percent),
+        ExecuteMsg::SetBuyFee {
+            tax_fee,
+            liquidity_fee,
+            burn_fee
+        } => execute_set_fee(deps, env, info, TransferKind::Buy, FeeProfile { tax_fee, liquidity_fee, burn_fee }),
+        ExecuteMsg::SetSellFee {
+            tax_fee,
+            liquidity_fee,
+            burn_fee
+        } => execute_set_fee(deps, env, info, TransferKind::Sell, FeeProfile { tax_fee, liquidity_fee, burn_fee }),
+        ExecuteMsg::SetTransferFee {
+            tax_fee,
+            liquidity_fee,
+            burn_fee
+        } => execute_set_fee(deps, env, info, TransferKind::Transfer, FeeProfile { tax_fee, liquidity_fee, burn_fee }),
+        ExecuteMsg::SetMarketingFee {
+            bps
+        } => execute_set_marketing_fee(deps, env, info, bps),
+        ExecuteMsg::SetMarketingAddress {
+            address
+        } => execute_set_marketing_address(deps, env, info, address),
         ExecuteMsg::SetMaxTxPercent {
             percent
         } => execute_set_max_tx_percent(deps, env, info, percent),
         ExecuteMsg::SetSwapAndLiquifyEnabled {
             enabled
         } => execute_set_swap_and_liquify_enabled(deps, env, info, enabled),
-        ExecuteMsg::FetchAdmin {} => execute_fetch_admin(deps, env, info)
+        ExecuteMsg::SetNumTokensSellToAddToLiquidity {
+            amount
+        } => execute_set_num_tokens_sell_to_add_to_liquidity(deps, env, info, amount),
+        ExecuteMsg::SetMaxLiquifyAmount {
+            amount
+        } => execute_set_max_liquify_amount(deps, env, info, amount),
+        ExecuteMsg::SetLiquifyInterval {
+            interval
+        } => execute_set_liquify_interval(deps, env, info, interval),
+        ExecuteMsg::Liquify {
+            amount
+        } => execute_liquify(deps, env, info, amount),
+        ExecuteMsg::SetLiquifySlippage {
+            max_slippage_bps,
+            swap_expiration
+        } => execute_set_liquify_slippage(deps, env, info, max_slippage_bps, swap_expiration),
+        ExecuteMsg::SetLpPolicy {
+            policy
+        } => execute_set_lp_policy(deps, env, info, policy),
+        ExecuteMsg::SetWithdrawCaps {
+            caps
+        } => execute_set_withdraw_caps(deps, env, info, caps),
+        ExecuteMsg::WithdrawLp {
+            amount,
+            recipient
+        } => execute_withdraw_lp(deps, env, info, amount, recipient),
+        ExecuteMsg::WithdrawNative {
+            amount,
+            recipient
+        } => execute_withdraw_native(deps, env, info, amount, recipient)
         
     }
 }
 
 
-pub fn execute_fetch_admin(
+pub fn execute_withdraw_lp(
     deps: DepsMut,
     env: Env,
-    info: MessageInfo
+    info: MessageInfo,
+    amount: Uint128,
+    recipient: Option<String>
 ) -> Result<Response, ContractError> {
 
-    _check_owner(&deps, &info)?;
+    _check_role(&deps, &info, Role::LiquidityManager)?;
 
     let cfg = CONFIG.load(deps.storage)?;
+    match &cfg.lp_policy {
+        LpPolicy::Hold => {},
+        LpPolicy::Lock { until } if until.is_expired(&env.block) => {},
+        LpPolicy::Lock { until } => return Err(ContractError::LpLocked { until: *until }),
+        policy => return Err(ContractError::LpNotWithdrawable { policy: policy.to_string() }),
+    }
+    let recipient = _withdrawal_recipient(&deps, &info, &cfg, recipient)?;
 
-    let contract_address = env.clone().contract.address.clone();
     // Get LP token address
-    let pool_response: InfoResponse = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-        contract_addr: cfg.pool_address.clone().into(),
-        msg: to_binary(&WasmswapQueryMsg::Info {})?,
-    }))?;
-    let lp_address = pool_response.lp_token_address;
-
-    //Get LP balance
-    let balance_response: CW20BalanceResponse = deps.querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-        contract_addr: lp_address.clone().into(),
-        msg: to_binary(&Cw20QueryMsg::Balance {address: contract_address.clone().into()})?,
-    }))?;
-    let lp_balance = balance_response.balance;
-    
-    //Get Juno Balance
-    let juno_response: BalanceResponse = deps.querier.query(&QueryRequest::Bank(BankQuery::Balance {
-        address: contract_address.clone().into(),
-        denom: cfg.denom.clone()
-    }))?;
-    let juno_balance = juno_response.amount.amount;
-
-    // create transfer cw20 msg
-    let transfer_msg = Cw20ExecuteMsg::Transfer {
-        recipient: info.sender.clone().into(),
-        amount: lp_balance,
-    };
-    let exec_transfer = WasmMsg::Execute {
-        contract_addr: lp_address.clone().into(),
-        msg: to_binary(&transfer_msg)?,
-        funds: vec![],
-    };
-    
-    let cw20_send_cosmos_msg: CosmosMsg = exec_transfer.into();
+    let pool = _primary_dex(deps.storage, &cfg)?.query_pool(deps.querier, &env, &cfg.pool_address, &cfg.denom)?;
+    let lp_token = Denom::Cw20(Addr::unchecked(pool.lp_token_address));
+
+    let msg = _withdraw(deps, &env, &info, lp_token, amount, cfg.withdraw_caps.lp_amount, &recipient)?;
+    Ok(Response::new()
+        .add_attribute("action", "withdraw_lp")
+        .add_attribute("recipient", recipient)
+        .add_attribute("amount", amount)
+        .add_message(msg))
+}
 
+pub fn execute_withdraw_native(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: Uint128,
+    recipient: Option<String>
+) -> Result<Response, ContractError> {
 
-    let transfer_bank_msg = cosmwasm_std::BankMsg::Send {
-        to_address: info.sender.clone().into(),
-        amount: vec![Coin{amount: juno_balance, denom: cfg.denom.clone()}],
-    };
-    let transfer_bank_cosmos_msg: CosmosMsg = transfer_bank_msg.into();
+    _check_role(&deps, &info, Role::LiquidityManager)?;
 
-    
-    
+    let cfg = CONFIG.load(deps.storage)?;
+    let recipient = _withdrawal_recipient(&deps, &info, &cfg, recipient)?;
 
-    Ok(Response::new().add_attribute("action", "fetch_admin")
-    .add_submessages(vec![SubMsg::new(cw20_send_cosmos_msg), SubMsg::new(transfer_bank_cosmos_msg)]))
+    let msg = _withdraw(deps, &env, &info, cfg.denom, amount, cfg.withdraw_caps.quote_amount, &recipient)?;
+    Ok(Response::new()
+        .add_attribute("action", "withdraw_native")
+        .add_attribute("recipient", recipient)
+        .add_attribute("amount", amount)
+        .add_message(msg))
+}
 
+/// Withdrawals go to the owner unless the owner picks another recipient
+pub fn _withdrawal_recipient(
+    deps: &DepsMut,
+    info: &MessageInfo,
+    cfg: &Config,
+    recipient: Option<String>
+) -> Result<Addr, ContractError> {
+    let owner = cfg.owner.clone().ok_or(ContractError::Frozen {})?;
+    match recipient {
+        None => Ok(owner),
+        Some(_) if info.sender != owner => Err(ContractError::Unauthorized {}),
+        Some(recipient) => Ok(deps.api.addr_validate(&recipient)?),
+    }
 }
+
+/// Records the withdrawal of `amount` of `asset` and returns its transfer.
+/// At most `cap` of `asset` can be withdrawn per `withdraw_caps.period`
+pub fn _withdraw(
+    deps: DepsMut,
+    env: &Env,
+    info: &MessageInfo,
+    asset: Denom,
+    amount: Uint128,
+    cap: Uint128,
+    recipient: &Addr
+) -> Result<CosmosMsg, ContractError> {
+    if amount.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+    let available = query_denom_balance(deps.querier, &asset, &env.contract.address)?;
+    if amount > available {
+        return Err(ContractError::WithdrawAmountTooHigh { available });
+    }
+    let cfg = CONFIG.load(deps.storage)?;
+    let withdrawn = _withdrawn_within(deps.storage, env, &asset, &cfg.withdraw_caps.period)?;
+    let available = cap.saturating_sub(withdrawn);
+    if amount > available {
+        return Err(ContractError::WithdrawCapExceeded { available });
+    }
+
+    let id = WITHDRAWAL_SEQ.may_load(deps.storage)?.unwrap_or_default() + 1;
+    WITHDRAWAL_SEQ.save(deps.storage, &id)?;
+    let msg = denom_transfer_msg(&asset, recipient, amount)?;
+    let withdrawal = Withdrawal {
+        id,
+        asset,
+        amount,
+        recipient: recipient.clone(),
+        by: info.sender.clone(),
+        height: env.block.height,
+        time: env.block.time,
+    };
+    WITHDRAWALS.save(deps.storage, id, &withdrawal)?;
+    Ok(msg)
+}
+
+/// Total of `asset` withdrawn within the last `period`
+pub fn _withdrawn_within(storage: &dyn Storage, env: &Env, asset: &Denom, period: &Duration) -> StdResult<Uint128> {
+    let mut withdrawn = Uint128::zero();
+    for item in WITHDRAWALS.range(storage, None, None, Order::Descending) {
+        let (_, withdrawal) = item?;
+        let recent = match period {
+            Duration::Height(blocks) => withdrawal.height.saturating_add(*blocks) > env.block.height,
+            Duration::Time(seconds) => withdrawal.time.plus_seconds(*seconds) > env.block.time,
+        };
+        if !recent {
+            break;
+        }
+        if withdrawal.asset == *asset {
+            withdrawn += withdrawal.amount;
+        }
+    }
+    Ok(withdrawn)
+}
+
 pub fn execute_exclude_from_reward(
     deps: DepsMut,
     _env: Env,
@@ -233,7 +397,7 @@ pub fn execute_exclude_from_reward(
     address: Addr
 ) -> Result<Response, ContractError> {
 
-    _check_owner(&deps, &info)?;
+    _check_role(&deps, &info, Role::ExclusionManager)?;
     
     if ISEXCLUDED.load(deps.storage, address.clone()).unwrap_or(false) {
         return Err(ContractError::AccountAlreadyExcluded {})
@@ -243,6 +407,7 @@ pub fn execute_exclude_from_reward(
     if  r_val > Uint128::zero() {
         let t_val = _token_from_reflection(deps.storage, r_val);
         TOWNED.save(deps.storage, address.clone(), &t_val)?;
+        _add_excluded_supply(deps.storage, r_val, t_val)?;
     }
 
     ISEXCLUDED.save(deps.storage, address.clone(), &true)?;
@@ -260,19 +425,30 @@ pub fn execute_include_in_reward(
     address: Addr
 ) -> Result<Response, ContractError> {
 
-    _check_owner(&deps, &info)?;
+    _check_role(&deps, &info, Role::ExclusionManager)?;
     
     if !ISEXCLUDED.load(deps.storage, address.clone()).unwrap_or(false) {
-        return Err(ContractError::AccountAlreadyExcluded {})
+        return Err(ContractError::AccountNotExcluded {})
     }
 
+    // rebuild the reflected balance at the current rate, while the account
+    // is still left out of the supply used to compute that rate
     let r_val = ROWNED.load(deps.storage, address.clone()).unwrap_or(Uint128::zero());
-    if  r_val > Uint128::zero() {
-        let t_val = _token_from_reflection(deps.storage, r_val);
-        TOWNED.save(deps.storage, address.clone(), &t_val)?;
-    }
+    let t_val = TOWNED.load(deps.storage, address.clone()).unwrap_or(Uint128::zero());
+    let current_rate = _get_rate(deps.storage);
+    let new_r_val = t_val * current_rate;
 
-    ISEXCLUDED.save(deps.storage, address.clone(), &true)?;
+    // the old reflected balance kept the rate from before the exclusion, so
+    // r_total has to follow the rebuilt one or it drifts from sum(ROWNED)
+    let mut cfg = CONFIG.load(deps.storage)?;
+    cfg.excluded_r_total -= r_val;
+    cfg.excluded_t_total -= t_val;
+    cfg.r_total = cfg.r_total - r_val + new_r_val;
+    CONFIG.save(deps.storage, &cfg)?;
+    ROWNED.save(deps.storage, address.clone(), &new_r_val)?;
+
+    TOWNED.remove(deps.storage, address.clone());
+    ISEXCLUDED.remove(deps.storage, address.clone());
 
     Ok(Response::new()
         .add_attribute("action", "include_in_reward")
@@ -287,7 +463,7 @@ pub fn execute_exclude_from_fee(
     address: Addr
 ) -> Result<Response, ContractError> {
 
-    _check_owner(&deps, &info)?;
+    _check_role(&deps, &info, Role::ExclusionManager)?;
     
     ISEXCLUDEDFROMFEE.save(deps.storage, address.clone(), &true)?;
     
@@ -304,7 +480,7 @@ pub fn execute_include_in_fee(
     address: Addr
 ) -> Result<Response, ContractError> {
 
-    _check_owner(&deps, &info)?;
+    _check_role(&deps, &info, Role::ExclusionManager)?;
     
     ISEXCLUDEDFROMFEE.save(deps.storage, address.clone(), &false)?;
     
@@ -314,184 +490,788 @@ pub fn execute_include_in_fee(
     )
 }
 
-pub fn execute_set_tax_fee_percent(
+pub fn execute_set_fee(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    kind: TransferKind,
+    fee: FeeProfile
+) -> Result<Response, ContractError> {
+
+    _check_role(&deps, &info, Role::FeeManager)?;
+
+    let cfg = CONFIG.load(deps.storage)?;
+    _check_fees(cfg.max_total_fee, &fee, cfg.marketing_fee)?;
+    
+    let pending = _queue_change(deps.storage, &env, ConfigChange::Fee { kind, fee: fee.clone() })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_fee")
+        .add_attribute("kind", kind.to_string())
+        .add_attribute("tax_fee", Uint128::from(fee.tax_fee))
+        .add_attribute("liquidity_fee", Uint128::from(fee.liquidity_fee))
+        .add_attribute("burn_fee", Uint128::from(fee.burn_fee))
+        .add_attribute("change_id", pending.id.to_string())
+        .add_attribute("executable_at", pending.executable_at.to_string())
+    )
+}
+
+pub fn execute_set_marketing_fee(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    bps: u16
+) -> Result<Response, ContractError> {
+
+    _check_role(&deps, &info, Role::FeeManager)?;
+
+    let mut cfg = CONFIG.load(deps.storage)?;
+    cfg.marketing_fee = bps;
+    _check_config_fees(deps.storage, &cfg)?;
+
+    let pending = _queue_change(deps.storage, &env, ConfigChange::MarketingFee { bps })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_marketing_fee")
+        .add_attribute("bps", Uint128::from(bps))
+        .add_attribute("change_id", pending.id.to_string())
+        .add_attribute("executable_at", pending.executable_at.to_string())
+    )
+}
+
+pub fn execute_set_marketing_address(
     deps: DepsMut,
     _env: Env,
     info: MessageInfo,
+    address: Option<String>
+) -> Result<Response, ContractError> {
+
+    _check_role(&deps, &info, Role::FeeManager)?;
+
+    let marketing_address = address
+        .map(|address| deps.api.addr_validate(&address))
+        .transpose()?;
+
+    let mut cfg = CONFIG.load(deps.storage)?;
+    cfg.marketing_address = marketing_address.clone();
+    CONFIG.save(deps.storage, &cfg)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_marketing_address")
+        .add_attribute("address", marketing_address.map(String::from).unwrap_or_default())
+    )
+}
+
+pub fn execute_set_max_tx_percent(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
     percent: u8
 ) -> Result<Response, ContractError> {
 
-    _check_owner(&deps, &info)?;
+    _check_role(&deps, &info, Role::FeeManager)?;
+    
+    let pending = _queue_change(deps.storage, &env, ConfigChange::MaxTxPercent { percent })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_max_tx_percent")
+        .add_attribute("percent", Uint128::from(percent))
+        .add_attribute("change_id", pending.id.to_string())
+        .add_attribute("executable_at", pending.executable_at.to_string())
+    )
+}
+
+pub fn execute_set_swap_and_liquify_enabled(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    enabled: bool
+) -> Result<Response, ContractError> {
+
+    _check_role(&deps, &info, Role::LiquidityManager)?;
     
     let mut cfg = CONFIG.load(deps.storage)?;
-    cfg.tax_fee = percent;
+    cfg.swap_and_liquidity_enabled = enabled;
     CONFIG.save(deps.storage, &cfg)?;
 
     Ok(Response::new()
-        .add_attribute("action", "set_tax_fee_percent")
-        .add_attribute("precent", Uint128::from(percent))
+        .add_attribute("action", "set_swap_and_liquify_enabled")
+        
     )
 }
 
-pub fn execute_set_liquidity_fee_percent(
+pub fn execute_set_num_tokens_sell_to_add_to_liquidity(
     deps: DepsMut,
     _env: Env,
     info: MessageInfo,
-    percent: u8
+    amount: Uint128
 ) -> Result<Response, ContractError> {
 
-    _check_owner(&deps, &info)?;
-    
+    _check_role(&deps, &info, Role::LiquidityManager)?;
+
     let mut cfg = CONFIG.load(deps.storage)?;
-    cfg.liquidity_fee = percent;
+    if amount > cfg.t_total {
+        return Err(ContractError::LiquifyAmountTooHigh {});
+    }
+    // a swap never sells less than the threshold that triggered it
+    if amount > cfg.max_liquify_amount {
+        return Err(ContractError::LiquifyCapBelowThreshold {});
+    }
+    cfg.num_tokens_sell_to_add_to_liquidity = amount;
     CONFIG.save(deps.storage, &cfg)?;
 
     Ok(Response::new()
-        .add_attribute("action", "set_liquidity_fee_percent")
-        .add_attribute("percent", Uint128::from(percent))
+        .add_attribute("action", "set_num_tokens_sell_to_add_to_liquidity")
+        .add_attribute("amount", amount)
     )
 }
 
-pub fn execute_set_max_tx_percent(
+pub fn execute_set_max_liquify_amount(
     deps: DepsMut,
     _env: Env,
     info: MessageInfo,
-    percent: u8
+    amount: Uint128
 ) -> Result<Response, ContractError> {
 
-    _check_owner(&deps, &info)?;
+    _check_role(&deps, &info, Role::LiquidityManager)?;
+
+    let mut cfg = CONFIG.load(deps.storage)?;
+    if amount > cfg.t_total {
+        return Err(ContractError::LiquifyAmountTooHigh {});
+    }
+    if amount < cfg.num_tokens_sell_to_add_to_liquidity {
+        return Err(ContractError::LiquifyCapBelowThreshold {});
+    }
+    cfg.max_liquify_amount = amount;
+    CONFIG.save(deps.storage, &cfg)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_max_liquify_amount")
+        .add_attribute("amount", amount)
+    )
+}
+
+pub fn execute_set_liquify_interval(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    interval: Duration
+) -> Result<Response, ContractError> {
+
+    _check_role(&deps, &info, Role::LiquidityManager)?;
+
+    let mut cfg = CONFIG.load(deps.storage)?;
+    cfg.liquify_interval = interval;
+    CONFIG.save(deps.storage, &cfg)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_liquify_interval")
+        .add_attribute("interval", interval.to_string())
+    )
+}
+
+/// Runs swap and liquify outside of a transfer, at most once per
+/// `liquify_interval`
+pub fn execute_liquify(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    amount: Option<Uint128>
+) -> Result<Response, ContractError> {
+
+    // no keeper can be granted once ownership is renounced, so liquify is
+    // left to anyone, still limited by the cooldown
+    let cfg = CONFIG.load(deps.storage)?;
+    if cfg.owner.is_some() {
+        _check_role(&deps, &info, Role::Keeper)?;
+    }
+    if !cfg.swap_and_liquidity_enabled {
+        return Err(ContractError::SwapAndLiquifyDisabled {});
+    }
+    if cfg.denom_side.is_none() {
+        return Err(ContractError::InvalidPool { pool: cfg.pool_address.to_string() });
+    }
+    if let Some(next_liquify) = NEXT_LIQUIFY.may_load(deps.storage)? {
+        if !next_liquify.is_expired(&env.block) {
+            return Err(ContractError::LiquifyCooldown { next_liquify });
+        }
+    }
+
+    let contract_token_balance = _contract_token_balance(deps.storage, &env)?;
+    let amount = amount
+        .unwrap_or(contract_token_balance)
+        .min(contract_token_balance)
+        .min(cfg.max_liquify_amount);
+    if amount.is_zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
+
+    NEXT_LIQUIFY.save(deps.storage, &cfg.liquify_interval.after(&env.block))?;
+    let msgs = swap_and_liquify(deps.storage, deps.querier, env, amount)?;
+
+    Ok(Response::new()
+        .add_submessages(msgs)
+        .add_attribute("action", "liquify")
+        .add_attribute("amount", amount)
+    )
+}
+
+pub fn execute_set_liquify_slippage(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    max_slippage_bps: u16,
+    swap_expiration: Option<Duration>
+) -> Result<Response, ContractError> {
+
+    _check_role(&deps, &info, Role::LiquidityManager)?;
+
+    if max_slippage_bps > BPS_DENOMINATOR {
+        return Err(ContractError::SlippageTooHigh {});
+    }
+
+    let mut cfg = CONFIG.load(deps.storage)?;
+    cfg.max_slippage_bps = max_slippage_bps;
+    cfg.swap_expiration = swap_expiration;
+    CONFIG.save(deps.storage, &cfg)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_liquify_slippage")
+        .add_attribute("max_slippage_bps", Uint128::from(max_slippage_bps))
+    )
+}
+
+pub fn execute_set_lp_policy(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    policy: LpPolicy
+) -> Result<Response, ContractError> {
+
+    _check_role(&deps, &info, Role::LiquidityManager)?;
+
+    _validate_lp_policy(deps.api, &policy)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    _check_lp_policy(&cfg.lp_policy, &policy, &env.block)?;
+
+    let pending = _queue_change(deps.storage, &env, ConfigChange::LpPolicy { policy: policy.clone() })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_lp_policy")
+        .add_attribute("policy", policy.to_string())
+        .add_attribute("change_id", pending.id.to_string())
+        .add_attribute("executable_at", pending.executable_at.to_string())
+    )
+}
+
+pub fn execute_set_withdraw_caps(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    caps: WithdrawCaps
+) -> Result<Response, ContractError> {
+
+    _check_role(&deps, &info, Role::LiquidityManager)?;
+
+    let pending = _queue_change(deps.storage, &env, ConfigChange::WithdrawCaps { caps: caps.clone() })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_withdraw_caps")
+        .add_attribute("period", caps.period.to_string())
+        .add_attribute("lp_amount", caps.lp_amount)
+        .add_attribute("quote_amount", caps.quote_amount)
+        .add_attribute("change_id", pending.id.to_string())
+        .add_attribute("executable_at", pending.executable_at.to_string())
+    )
+}
+
+pub fn _validate_lp_policy(api: &dyn Api, policy: &LpPolicy) -> StdResult<()> {
+    if let Some(recipient) = policy.recipient() {
+        api.addr_validate(recipient.as_str())?;
+    }
+    Ok(())
+}
+
+/// An active lock can only be replaced by a lock ending no earlier
+pub fn _check_lp_policy(current: &LpPolicy, policy: &LpPolicy, block: &BlockInfo) -> Result<(), ContractError> {
+    let until = match current {
+        LpPolicy::Lock { until } if !until.is_expired(block) => until,
+        _ => return Ok(()),
+    };
+    match policy {
+        LpPolicy::Lock { until: new_until }
+            if matches!(new_until.partial_cmp(until), Some(Ordering::Greater) | Some(Ordering::Equal)) => Ok(()),
+        _ => Err(ContractError::LpLocked { until: *until }),
+    }
+}
+
+pub fn execute_deliver(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+    t_amount: Uint128
+) -> Result<Response, ContractError> {
     
     let mut cfg = CONFIG.load(deps.storage)?;
-    cfg.max_tx_amount = cfg.t_total * Uint128::from(percent as u32) / Uint128::from(100u128);
+    if !ISEXCLUDED.load(deps.storage, info.sender.clone()).unwrap_or(false) {
+        return Err(ContractError::ExcludedDisableDeliver {})
+    }
+
+    let (r_amount, _r_transfer_amount, _r_fee, _r_burn, _t_transfer_amount, _t_fee, _t_liquidity, _t_burn, _t_marketing) = _get_values(deps.storage, t_amount, &FeeContext::new(&cfg, TransferKind::Transfer, None, true));
+    let r_val = ROWNED.load(deps.storage, info.sender.clone()).unwrap_or(Uint128::zero());
+    ROWNED.save(deps.storage, info.sender.clone(), &(r_val - r_amount))?;
+
+    if ISEXCLUDED.load(deps.storage, info.sender.clone()).unwrap_or(false) {
+        cfg.excluded_r_total -= r_amount;
+    }
+    cfg.r_total -= r_amount;
+    cfg.t_fee_total += t_amount;
     CONFIG.save(deps.storage, &cfg)?;
 
     Ok(Response::new()
-        .add_attribute("action", "set_max_tx_percent")
-        .add_attribute("percent", Uint128::from(percent))
+        .add_attribute("action", "deliver")
+        .add_attribute("t_amount", t_amount)
+    )
+}
+
+pub fn _check_owner(
+    deps: &DepsMut,
+    info: &MessageInfo
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let owner = cfg.owner.ok_or(ContractError::Frozen {})?;
+    if info.sender != owner {
+        return Err(ContractError::Unauthorized {})
+    }
+    Ok(Response::new().add_attribute("action", "check_owner"))
+}
+
+/// Passes for the owner and for any address granted `role`
+pub fn _check_role(
+    deps: &DepsMut,
+    info: &MessageInfo,
+    role: Role
+) -> Result<Response, ContractError> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let owner = cfg.owner.ok_or(ContractError::Frozen {})?;
+    if info.sender != owner {
+        let roles = ROLES.may_load(deps.storage, info.sender.clone())?.unwrap_or_default();
+        if !roles.contains(&role) {
+            return Err(ContractError::Unauthorized {})
+        }
+    }
+    Ok(Response::new().add_attribute("action", "check_role"))
+}
+
+pub fn execute_grant_role(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+    role: Role
+) -> Result<Response, ContractError> {
+    // authorize owner
+    _check_owner(&deps, &info)?;
+
+    let addr = deps.api.addr_validate(&address)?;
+    let mut roles = ROLES.may_load(deps.storage, addr.clone())?.unwrap_or_default();
+    if !roles.contains(&role) {
+        roles.push(role.clone());
+        ROLES.save(deps.storage, addr, &roles)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "grant_role")
+        .add_attribute("address", address)
+        .add_attribute("role", role.to_string())
+    )
+}
+
+pub fn execute_revoke_role(
+    deps: DepsMut,
+    info: MessageInfo,
+    address: String,
+    role: Role
+) -> Result<Response, ContractError> {
+    // authorize owner
+    _check_owner(&deps, &info)?;
+
+    let addr = deps.api.addr_validate(&address)?;
+    let mut roles = ROLES.may_load(deps.storage, addr.clone())?.unwrap_or_default();
+    roles.retain(|r| r != &role);
+    if roles.is_empty() {
+        ROLES.remove(deps.storage, addr);
+    } else {
+        ROLES.save(deps.storage, addr, &roles)?;
+    }
+
+    Ok(Response::new()
+        .add_attribute("action", "revoke_role")
+        .add_attribute("address", address)
+        .add_attribute("role", role.to_string())
     )
 }
 
-pub fn execute_set_swap_and_liquify_enabled(
+
+pub fn execute_propose_new_owner(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    owner: String,
+    expiry: Option<Expiration>,
+) -> Result<Response, ContractError> {
+    // authorize owner
+    _check_owner(&deps, &info)?;
+
+    let new_addr = deps.api.addr_validate(&owner)?;
+    if let Some(expiry) = expiry {
+        if expiry.is_expired(&env.block) {
+            return Err(ContractError::OwnershipProposalExpired {});
+        }
+    }
+
+    OWNERSHIP_PROPOSAL.save(deps.storage, &OwnershipProposal {
+        owner: new_addr,
+        expiry,
+    })?;
+
+    Ok(Response::new().add_attribute("action", "propose_new_owner").add_attribute("owner", owner))
+}
+
+pub fn execute_accept_ownership(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let proposal = OWNERSHIP_PROPOSAL
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoOwnershipProposal {})?;
+
+    if info.sender != proposal.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if let Some(expiry) = proposal.expiry {
+        if expiry.is_expired(&env.block) {
+            return Err(ContractError::OwnershipProposalExpired {});
+        }
+    }
+
+    CONFIG.update(deps.storage, |mut exists| -> StdResult<_> {
+        exists.owner = Some(proposal.owner.clone());
+        Ok(exists)
+    })?;
+    OWNERSHIP_PROPOSAL.remove(deps.storage);
+
+    Ok(Response::new().add_attribute("action", "accept_ownership").add_attribute("owner", proposal.owner))
+}
+
+pub fn execute_cancel_ownership_proposal(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    // authorize owner
+    _check_owner(&deps, &info)?;
+
+    if OWNERSHIP_PROPOSAL.may_load(deps.storage)?.is_none() {
+        return Err(ContractError::NoOwnershipProposal {});
+    }
+    OWNERSHIP_PROPOSAL.remove(deps.storage);
+
+    Ok(Response::new().add_attribute("action", "cancel_ownership_proposal"))
+}
+
+pub fn execute_renounce_ownership(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    // authorize owner
+    _check_owner(&deps, &info)?;
+
+    CONFIG.update(deps.storage, |mut exists| -> StdResult<_> {
+        exists.owner = None;
+        Ok(exists)
+    })?;
+    OWNERSHIP_PROPOSAL.remove(deps.storage);
+
+    Ok(Response::new().add_attribute("action", "renounce_ownership"))
+}
+
+pub fn update_pool(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    address: Addr,
+    dex: Option<Dex>,
+) -> Result<Response, ContractError> {
+    _check_role(&deps, &info, Role::LiquidityManager)?;
+
+    let cfg = CONFIG.load(deps.storage)?;
+    let dex = match dex {
+        Some(dex) => dex,
+        None => _primary_dex(deps.storage, &cfg)?,
+    };
+    dex.query_pool(deps.querier, &env, &address, &cfg.denom)?;
+
+    let pending = _queue_change(deps.storage, &env, ConfigChange::PoolAddress { address: address.clone(), dex: dex.clone() })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "update_pool")
+        .add_attribute("address", address.clone())
+        .add_attribute("dex", dex.to_string())
+        .add_attribute("change_id", pending.id.to_string())
+        .add_attribute("executable_at", pending.executable_at.to_string()))
+}
+
+pub fn execute_add_amm_pair(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    address: String,
+    dex: Dex,
+    buy_fee: Option<FeeProfile>,
+    sell_fee: Option<FeeProfile>,
+) -> Result<Response, ContractError> {
+    _check_role(&deps, &info, Role::LiquidityManager)?;
+
+    let cfg = CONFIG.load(deps.storage)?;
+    let address = deps.api.addr_validate(&address)?;
+    dex.query_pool(deps.querier, &env, &address, &cfg.denom)?;
+    for fee in buy_fee.iter().chain(sell_fee.iter()) {
+        _check_fees(cfg.max_total_fee, fee, cfg.marketing_fee)?;
+    }
+
+    let pair = AmmPair { dex: dex.clone(), buy_fee, sell_fee };
+    let pending = _queue_change(deps.storage, &env, ConfigChange::AddAmmPair { address: address.clone(), pair })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "add_amm_pair")
+        .add_attribute("address", address)
+        .add_attribute("dex", dex.to_string())
+        .add_attribute("change_id", pending.id.to_string())
+        .add_attribute("executable_at", pending.executable_at.to_string()))
+}
+
+pub fn execute_remove_amm_pair(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    address: String,
+) -> Result<Response, ContractError> {
+    _check_role(&deps, &info, Role::LiquidityManager)?;
+
+    let cfg = CONFIG.load(deps.storage)?;
+    let address = deps.api.addr_validate(&address)?;
+    if address == cfg.pool_address {
+        return Err(ContractError::CannotRemovePrimaryPool {});
+    }
+    if !AMM_PAIRS.has(deps.storage, address.clone()) {
+        return Err(ContractError::InvalidPool { pool: address.to_string() });
+    }
+
+    let pending = _queue_change(deps.storage, &env, ConfigChange::RemoveAmmPair { address: address.clone() })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "remove_amm_pair")
+        .add_attribute("address", address)
+        .add_attribute("change_id", pending.id.to_string())
+        .add_attribute("executable_at", pending.executable_at.to_string()))
+}
+
+/// Queues `change` until the configured timelock has passed
+pub fn _queue_change(storage: &mut dyn Storage, env: &Env, change: ConfigChange) -> StdResult<PendingChange> {
+    let cfg = CONFIG.load(storage)?;
+    let id = PENDING_CHANGE_SEQ.may_load(storage)?.unwrap_or_default() + 1;
+    PENDING_CHANGE_SEQ.save(storage, &id)?;
+
+    let pending = PendingChange {
+        id,
+        change,
+        executable_at: cfg.timelock.after(&env.block),
+    };
+    PENDING_CHANGES.save(storage, id, &pending)?;
+    Ok(pending)
+}
+
+pub fn execute_pending_change(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
-    enabled: bool
+    id: u64,
 ) -> Result<Response, ContractError> {
+    let pending = PENDING_CHANGES.load(deps.storage, id)?;
+    _check_role(&deps, &info, pending.change.role())?;
+
+    if !pending.executable_at.is_expired(&env.block) {
+        return Err(ContractError::Timelocked { executable_at: pending.executable_at });
+    }
 
-    _check_owner(&deps, &info)?;
-    
     let mut cfg = CONFIG.load(deps.storage)?;
-    cfg.swap_and_liquidity_enabled = enabled;
+    let mut messages: Vec<CosmosMsg> = vec![];
+    match pending.change {
+        ConfigChange::Fee { kind, fee } => *cfg.fee_profile_mut(kind) = fee,
+        ConfigChange::MarketingFee { bps } => cfg.marketing_fee = bps,
+        ConfigChange::MaxTxPercent { percent } => {
+            cfg.max_tx_amount = cfg.t_total * Uint128::from(percent as u32) / Uint128::from(100u128);
+        },
+        ConfigChange::PoolAddress { address, dex } => {
+            // the pool may have been changed since the change was queued
+            let pool = dex.query_pool(deps.querier, &env, &address, &cfg.denom)?;
+            cfg.denom_side = Some(pool.denom_side);
+            // the previous pool stays registered, see RemoveAmmPair
+            let pair = match AMM_PAIRS.may_load(deps.storage, address.clone())? {
+                Some(pair) => AmmPair { dex, ..pair },
+                None => AmmPair { dex, buy_fee: None, sell_fee: None },
+            };
+            AMM_PAIRS.save(deps.storage, address.clone(), &pair)?;
+            cfg.pool_address = address;
+        },
+        ConfigChange::AddAmmPair { address, pair } => {
+            let pool = pair.dex.query_pool(deps.querier, &env, &address, &cfg.denom)?;
+            if address == cfg.pool_address {
+                cfg.denom_side = Some(pool.denom_side);
+            }
+            AMM_PAIRS.save(deps.storage, address, &pair)?;
+        },
+        ConfigChange::RemoveAmmPair { address } => {
+            if address == cfg.pool_address {
+                return Err(ContractError::CannotRemovePrimaryPool {});
+            }
+            AMM_PAIRS.remove(deps.storage, address);
+        },
+        ConfigChange::LpPolicy { policy } => {
+            // the lock may have been extended since the change was queued
+            _check_lp_policy(&cfg.lp_policy, &policy, &env.block)?;
+            // LP tokens held under the previous policy follow the new
+            // recipient, WithdrawLp no longer takes them. Without a
+            // checked pool no liquidity was ever added
+            if let (Some(recipient), Some(_)) = (policy.recipient(), &cfg.denom_side) {
+                let pool = _primary_dex(deps.storage, &cfg)?.query_pool(deps.querier, &env, &cfg.pool_address, &cfg.denom)?;
+                let lp_token = Denom::Cw20(Addr::unchecked(pool.lp_token_address));
+                let held = query_denom_balance(deps.querier, &lp_token, &env.contract.address)?;
+                if !held.is_zero() {
+                    messages.push(denom_transfer_msg(&lp_token, recipient, held)?);
+                }
+            }
+            cfg.lp_policy = policy;
+        },
+        ConfigChange::WithdrawCaps { caps } => cfg.withdraw_caps = caps,
+    }
+    // another fee change may have been executed since this one was queued
+    _check_config_fees(deps.storage, &cfg)?;
     CONFIG.save(deps.storage, &cfg)?;
+    PENDING_CHANGES.remove(deps.storage, id);
 
     Ok(Response::new()
-        .add_attribute("action", "set_swap_and_liquify_enabled")
-        
-    )
+        .add_attribute("action", "execute_pending_change")
+        .add_attribute("change_id", id.to_string())
+        .add_messages(messages))
 }
 
-pub fn execute_deliver(
+pub fn execute_cancel_pending_change(
     deps: DepsMut,
-    _env: Env,
     info: MessageInfo,
-    t_amount: Uint128
+    id: u64,
 ) -> Result<Response, ContractError> {
-    
-    let mut cfg = CONFIG.load(deps.storage)?;
-    if !ISEXCLUDED.load(deps.storage, info.sender.clone()).unwrap_or(false) {
-        return Err(ContractError::ExcludedDisableDeliver {})
-    }
+    let pending = PENDING_CHANGES.load(deps.storage, id)?;
+    _check_role(&deps, &info, pending.change.role())?;
 
-    let (r_amount, _r_transfer_amount, _r_fee, _t_transfer_amount, _t_fee, _t_liquidity) = _get_values(deps.storage, t_amount);
-    let r_val = ROWNED.load(deps.storage, info.sender.clone()).unwrap_or(Uint128::zero());
-    ROWNED.save(deps.storage, info.sender.clone(), &(r_val - r_amount))?;
-
-    cfg.r_total -= r_amount;
-    cfg.t_fee_total += t_amount;
-    CONFIG.save(deps.storage, &cfg)?;
+    PENDING_CHANGES.remove(deps.storage, id);
 
     Ok(Response::new()
-        .add_attribute("action", "deliver")
-        .add_attribute("t_amount", t_amount)
-    )
+        .add_attribute("action", "cancel_pending_change")
+        .add_attribute("change_id", id.to_string()))
 }
 
-pub fn _check_owner(
-    deps: &DepsMut,
-    info: &MessageInfo
-) -> Result<Response, ContractError> {
-    let cfg = CONFIG.load(deps.storage)?;
-    if info.sender != cfg.owner {
-        return Err(ContractError::Unauthorized {})
+/// Rejects fees adding up to more than `max_total_fee` basis points
+pub fn _check_fees(max_total_fee: u16, fee: &FeeProfile, marketing_fee: u16) -> Result<(), ContractError> {
+    if fee.total() + marketing_fee as u32 > max_total_fee as u32 {
+        return Err(ContractError::FeeTooHigh {});
     }
-    Ok(Response::new().add_attribute("action", "check_owner"))
+    Ok(())
 }
 
+pub fn _check_config_fees(storage: &dyn Storage, cfg: &Config) -> Result<(), ContractError> {
+    for kind in [TransferKind::Buy, TransferKind::Sell, TransferKind::Transfer] {
+        _check_fees(cfg.max_total_fee, cfg.fee_profile(kind), cfg.marketing_fee)?;
+    }
+    for item in AMM_PAIRS.range(storage, None, None, Order::Ascending) {
+        let (_, pair) = item?;
+        for fee in pair.buy_fee.iter().chain(pair.sell_fee.iter()) {
+            _check_fees(cfg.max_total_fee, fee, cfg.marketing_fee)?;
+        }
+    }
+    Ok(())
+}
 
-pub fn update_config(
-    deps: DepsMut,
-    info: MessageInfo,
-    new_owner: String,
-) -> Result<Response, ContractError> {
-    // authorize owner
-    _check_owner(&deps, &info)?;
-
-    let new_addr = deps.api.addr_validate(&new_owner)?;
-
-    CONFIG.update(deps.storage, |mut exists| -> StdResult<_> {
-        exists.owner = new_addr.clone();
-        Ok(exists)
-    })?;
+/// Dex of the primary pool
+pub fn _primary_dex(storage: &dyn Storage, cfg: &Config) -> StdResult<Dex> {
+    Ok(AMM_PAIRS.load(storage, cfg.pool_address.clone())?.dex)
+}
 
-    Ok(Response::new().add_attribute("action", "update_config").add_attribute("owner", new_owner))
+/// Fees applied to a single transfer. Exempt transfers get a zero context
+/// rather than zeroing the stored config
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FeeContext {
+    pub fee: FeeProfile,
+    pub marketing_fee: u16,
 }
 
-pub fn update_pool(
-    deps: DepsMut,
-    info: MessageInfo,
-    address: Addr,
-) -> Result<Response, ContractError> {
-    // authorize owner
-    _check_owner(&deps, &info)?;
+impl FeeContext {
+    /// `pair` is the pool bought from or sold to, whose fees replace the
+    /// config ones when set
+    pub fn new(cfg: &Config, kind: TransferKind, pair: Option<&AmmPair>, take_fee: bool) -> Self {
+        if !take_fee {
+            return FeeContext::default();
+        }
+        let fee = pair
+            .and_then(|pair| pair.fee_profile(kind))
+            .unwrap_or_else(|| cfg.fee_profile(kind));
+        FeeContext {
+            fee: fee.clone(),
+            marketing_fee: cfg.marketing_fee,
+        }
+    }
+}
 
-    CONFIG.update(deps.storage, |mut exists| -> StdResult<_> {
-        exists.pool_address = address.clone();
-        Ok(exists)
-    })?;
+pub fn _calculate_tax_fee(amount: Uint128, fees: &FeeContext) -> Uint128 {
+    return amount * Uint128::from(fees.fee.tax_fee) / Uint128::from(BPS_DENOMINATOR);
+}
 
-    Ok(Response::new().add_attribute("action", "update_pool").add_attribute("address", address.clone()))
+pub fn _calculate_liquidity_fee(amount: Uint128, fees: &FeeContext) -> Uint128 {
+    return amount * Uint128::from(fees.fee.liquidity_fee) / Uint128::from(BPS_DENOMINATOR);
 }
 
-pub fn _calculate_tax_fee(storage: &mut dyn Storage, amount: Uint128) -> Uint128 {
-    let cfg = CONFIG.load(storage).unwrap();
-    return amount * Uint128::from(cfg.tax_fee as u128) / Uint128::from(100u128);
+pub fn _calculate_burn_fee(amount: Uint128, fees: &FeeContext) -> Uint128 {
+    return amount * Uint128::from(fees.fee.burn_fee) / Uint128::from(BPS_DENOMINATOR);
 }
 
-pub fn _calculate_liquidity_fee(storage: &mut dyn Storage, amount: Uint128) -> Uint128 {
-    let cfg = CONFIG.load(storage).unwrap();
-    return amount * Uint128::from(cfg.liquidity_fee as u128) / Uint128::from(100u128);
+pub fn _calculate_marketing_fee(amount: Uint128, fees: &FeeContext) -> Uint128 {
+    return amount * Uint128::from(fees.marketing_fee) / Uint128::from(BPS_DENOMINATOR);
 }
 
-pub fn _get_t_values(storage: &mut dyn Storage, t_amount: Uint128) -> (Uint128, Uint128, Uint128) {
-    let t_fee = _calculate_tax_fee(storage, t_amount);
-    let t_liquidity = _calculate_liquidity_fee(storage, t_amount);
-    let t_transfer_amount = t_amount - t_fee - t_liquidity;
-    return (t_transfer_amount, t_fee, t_liquidity);
+pub fn _get_t_values(t_amount: Uint128, fees: &FeeContext) -> (Uint128, Uint128, Uint128, Uint128, Uint128) {
+    let t_fee = _calculate_tax_fee(t_amount, fees);
+    let t_liquidity = _calculate_liquidity_fee(t_amount, fees);
+    let t_burn = _calculate_burn_fee(t_amount, fees);
+    let t_marketing = _calculate_marketing_fee(t_amount, fees);
+    let t_transfer_amount = t_amount - t_fee - t_liquidity - t_burn - t_marketing;
+    return (t_transfer_amount, t_fee, t_liquidity, t_burn, t_marketing);
 }
 
-pub fn _get_r_values(t_amount: Uint128, t_fee: Uint128, t_liquidity: Uint128, current_rate: Uint128) -> (Uint128, Uint128, Uint128) {
+pub fn _get_r_values(t_amount: Uint128, t_fee: Uint128, t_liquidity: Uint128, t_burn: Uint128, t_marketing: Uint128, current_rate: Uint128) -> (Uint128, Uint128, Uint128, Uint128) {
     let r_amount = t_amount * current_rate;
     let r_fee = t_fee * current_rate;
     let r_liquidity = t_liquidity * current_rate;
-    let r_transfer_amount = r_amount - r_fee - r_liquidity;
-    return (r_amount, r_transfer_amount, r_fee);
+    let r_burn = t_burn * current_rate;
+    let r_marketing = t_marketing * current_rate;
+    let r_transfer_amount = r_amount - r_fee - r_liquidity - r_burn - r_marketing;
+    return (r_amount, r_transfer_amount, r_fee, r_burn);
 }
 
-pub fn _get_values(storage: &mut dyn Storage, t_amount: Uint128) -> (Uint128, Uint128, Uint128, Uint128, Uint128, Uint128) {
-    let (t_transfer_amount, t_fee, t_liquidity) = _get_t_values(storage, t_amount);
+pub fn _get_values(storage: &mut dyn Storage, t_amount: Uint128, fees: &FeeContext) -> (Uint128, Uint128, Uint128, Uint128, Uint128, Uint128, Uint128, Uint128, Uint128) {
+    let (t_transfer_amount, t_fee, t_liquidity, t_burn, t_marketing) = _get_t_values(t_amount, fees);
     let current_rate = _get_rate(storage);
-    let (r_amount, r_transfer_amount, r_fee) = _get_r_values( t_amount, t_fee, t_liquidity, current_rate);
-    return (r_amount, r_transfer_amount, r_fee, t_transfer_amount, t_fee, t_liquidity);
+    let (r_amount, r_transfer_amount, r_fee, r_burn) = _get_r_values( t_amount, t_fee, t_liquidity, t_burn, t_marketing, current_rate);
+    return (r_amount, r_transfer_amount, r_fee, r_burn, t_transfer_amount, t_fee, t_liquidity, t_burn, t_marketing);
 }
 
 pub fn _take_liquidity(storage: &mut dyn Storage, env: Env, t_liquidity: Uint128) -> StdResult<Response> {
@@ -504,11 +1284,26 @@ pub fn _take_liquidity(storage: &mut dyn Storage, env: Env, t_liquidity: Uint128
     if  ISEXCLUDED.load(storage, contract_addr.clone()).unwrap_or(false) {
         let t_val = TOWNED.load(storage, contract_addr.clone()).unwrap_or(Uint128::zero()) + t_liquidity;
         TOWNED.save(storage, contract_addr.clone(), &t_val)?;
+        _add_excluded_supply(storage, r_liquidity, t_liquidity)?;
     }
     Ok(Response::default())
     
 }
 
+/// Credits the marketing share to the contract like liquidity, and books it
+/// for the treasury payout in `swap_and_liquify`
+pub fn _take_marketing(storage: &mut dyn Storage, env: Env, t_marketing: Uint128) -> StdResult<Response> {
+    if t_marketing.is_zero() {
+        return Ok(Response::default());
+    }
+    _take_liquidity(storage, env, t_marketing)?;
+
+    let mut cfg = CONFIG.load(storage)?;
+    cfg.marketing_tokens += t_marketing;
+    CONFIG.save(storage, &cfg)?;
+    Ok(Response::default())
+}
+
 pub fn _reflect_fee(storage: &mut dyn Storage, r_fee: Uint128, t_fee: Uint128) -> StdResult<Response> {
     let mut cfg = CONFIG.load(storage)?;
     cfg.r_total -= r_fee;
@@ -517,6 +1312,40 @@ pub fn _reflect_fee(storage: &mut dyn Storage, r_fee: Uint128, t_fee: Uint128) -
     Ok(Response::default())
 }
 
+/// Takes burned tokens out of circulation for good
+pub fn _burn_fee(storage: &mut dyn Storage, r_burn: Uint128, t_burn: Uint128) -> StdResult<Response> {
+    if t_burn.is_zero() {
+        return Ok(Response::default());
+    }
+    let mut cfg = CONFIG.load(storage)?;
+    cfg.r_total -= r_burn;
+    cfg.t_total -= t_burn;
+    cfg.t_burn_total += t_burn;
+    CONFIG.save(storage, &cfg)?;
+
+    TOKEN_INFO.update(storage, |mut info| -> StdResult<_> {
+        info.total_supply -= t_burn;
+        Ok(info)
+    })?;
+    Ok(Response::default())
+}
+
+pub fn _add_excluded_supply(storage: &mut dyn Storage, r_amount: Uint128, t_amount: Uint128) -> StdResult<Response> {
+    let mut cfg = CONFIG.load(storage)?;
+    cfg.excluded_r_total += r_amount;
+    cfg.excluded_t_total += t_amount;
+    CONFIG.save(storage, &cfg)?;
+    Ok(Response::default())
+}
+
+pub fn _sub_excluded_supply(storage: &mut dyn Storage, r_amount: Uint128, t_amount: Uint128) -> StdResult<Response> {
+    let mut cfg = CONFIG.load(storage)?;
+    cfg.excluded_r_total -= r_amount;
+    cfg.excluded_t_total -= t_amount;
+    CONFIG.save(storage, &cfg)?;
+    Ok(Response::default())
+}
+
 //swap and liquify
 
 pub fn swap_and_liquify(
@@ -524,120 +1353,92 @@ pub fn swap_and_liquify(
     querier: QuerierWrapper,
     env: Env,
     contract_token_balance: Uint128
-) -> Result<Vec<CosmosMsg>, ContractError> {
+) -> Result<Vec<SubMsg>, ContractError> {
+    // the marketing share is sold along with the first half and its
+    // proceeds go to the treasury
+    let cfg = CONFIG.load(storage)?;
+    let marketing_amount = match cfg.marketing_address {
+        Some(_) => cfg.marketing_tokens.min(contract_token_balance),
+        None => Uint128::zero(),
+    };
+    let liquify_amount = contract_token_balance - marketing_amount;
+
     // split the contract balance into halves
-    let half = contract_token_balance / Uint128::from(2u128);
-    let other_half = contract_token_balance - half;
+    let half = liquify_amount / Uint128::from(2u128);
+    let other_half = liquify_amount - half;
+    let swap_amount = half + marketing_amount;
 
-    let mut messages: Vec<CosmosMsg> = vec![];
+    let mut messages: Vec<SubMsg> = vec![];
+    if cfg.denom_side.is_none() || swap_amount.is_zero() {
+        return Ok(messages);
+    }
     // capture the contract's current Juno balance.
     // this is so that we can capture exactly the amount of Juno that the
     // swap creates, and not make the liquidity event include any Juno that
     // has been manually sent to the contract
-    let cfg = CONFIG.load(storage)?;
-    let contract_addr = env.contract.address.clone();
-    let native_balance: BalanceResponse = querier.query(&QueryRequest::Bank(BankQuery::Balance {
-        address: contract_addr.clone().into(),
-        denom: cfg.denom.clone()
-    }))?;
-    let initial_balance = native_balance.amount.amount;
+    let initial_balance = query_denom_balance(querier, &cfg.denom, &env.contract.address)?;
 
     // swap tokens for Juno
-    // swap_tokens_for_juno(storage, querier, env.clone(), half)?;
-    // {
-        let cfg = CONFIG.load(storage)?;
-        // generate the uniswap pair path of token -> juno
-        //query token1.reserve and token2.reserve
-        let info_response: InfoResponse = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-            contract_addr: cfg.pool_address.clone().into(),
-            msg: to_binary(&WasmswapQueryMsg::Info {})?,
-        }))?;
-
-        //Increase allowance
-        let increase_allowance_swap_msg = ExecuteMsg::IncreaseAllowance {
-            spender: cfg.pool_address.clone().into(),
-            amount: half,
-            expires: None 
-        };
-        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: env.contract.address.clone().into(),
-            msg: to_binary(&increase_allowance_swap_msg)?,
-            funds: vec![],
-        }));
-
-        //input token is token2
-        let juno_bought = _get_input_price(half, info_response.token2_reserve, info_response.token1_reserve)?;
-        // Juno is Token1, Safemoon is Token2
-        let swap_msg = WasmswapExecuteMsg::Swap {
-            input_token: TokenSelect::Token2,
-            input_amount: half,
-            min_output: juno_bought,
-            expiration: None
-        };
-        let callback = CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: cfg.pool_address.clone().into(),
-            msg: to_binary(&swap_msg)?,
-            funds: vec![],
-        });
-        messages.push(callback);
-    // }
+    messages.append(&mut swap_tokens_for_juno(storage, querier, env.clone(), swap_amount)?);
 
-    // how much juno did we just swap into?
-    // let native_balance_new: BalanceResponse = querier.query(&QueryRequest::Bank(BankQuery::Balance {
-    //     address: contract_addr.clone().into(),
-    //     denom: cfg.denom.clone()
-    // }))?;
-    // let new_balance = native_balance_new.amount.amount - initial_balance;
+    let mut cfg = CONFIG.load(storage)?;
+    cfg.marketing_tokens -= marketing_amount;
+    CONFIG.save(storage, &cfg)?;
 
-    // add liquidity to uniswap
-    // add_liquidity(storage, querier, env.clone(), other_half, new_balance)?;
-    // {
-        let cfg = CONFIG.load(storage)?;
+    PENDING_LIQUIFY.save(storage, &PendingLiquify {
+        initial_balance,
+        swap_amount,
+        marketing_amount,
+        token_amount: other_half,
+    })?;
+    Ok(messages)
+}
 
-        
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        LIQUIFY_SWAP_REPLY_ID => reply_liquify_swap(deps, env),
+        LIQUIFY_PROVIDE_REPLY_ID => reply_liquify_provide(deps, env),
+        id => Err(ContractError::UnknownReplyId { id }),
+    }
+}
 
-        let token2_amount = _get_token2_amount_required(
-            juno_bought,
-            info_response.token2_reserve + half,
-            info_response.token1_reserve - juno_bought
-        )?;
+/// Second half of `swap_and_liquify`: pays the treasury its share of what the
+/// swap actually returned and adds the rest as liquidity
+pub fn reply_liquify_swap(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let pending = PENDING_LIQUIFY.load(deps.storage)?;
+    PENDING_LIQUIFY.remove(deps.storage);
+    let cfg = CONFIG.load(deps.storage)?;
 
-        let increase_allowance_msg = ExecuteMsg::IncreaseAllowance {
-            spender: cfg.pool_address.clone().into(),
-            amount: token2_amount,
-            expires: None 
-        };
-        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: env.contract.address.clone().into(),
-            msg: to_binary(&increase_allowance_msg)?,
-            funds: vec![],
-        }));
-    
-        // let max_token2 = _get_input_price(juno_bought, info_response.token1_reserve, info_response.token2_reserve)?;
-        let add_liquidity_msg = WasmswapExecuteMsg::AddLiquidity {
-            token1_amount: juno_bought,
-            max_token2: token2_amount,
-            min_liquidity: Uint128::from(1u128),
-            expiration: None
-        };
-        let funds = Coin {
-            amount: juno_bought,
-            denom: cfg.denom,
-        };
-        messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: cfg.pool_address.clone().into(),
-            msg: to_binary(&add_liquidity_msg)?,
-            funds: vec![funds],
-        }));
+    // how much juno did we just swap into?
+    let new_balance = query_denom_balance(deps.querier, &cfg.denom, &env.contract.address)?
+        .checked_sub(pending.initial_balance)
+        .map_err(StdError::overflow)?;
 
-    // }
-    Ok(messages)
-    
-    // Ok(Response::new()
-    //     .add_messages(messages)
-    //     .add_attribute("action", "swap_and_liquify")
-    //     .add_attribute("contract_token_balance", contract_token_balance)
-    // )
+    let mut res = Response::new()
+        .add_attribute("action", "swap_and_liquify")
+        .add_attribute("swapped", pending.swap_amount)
+        .add_attribute("received", new_balance);
+
+    // pay the treasury its share of the swap
+    let marketing_juno = match &cfg.marketing_address {
+        Some(_) => new_balance.multiply_ratio(pending.marketing_amount, pending.swap_amount),
+        None => Uint128::zero(),
+    };
+    if !marketing_juno.is_zero() {
+        res = res.add_message(denom_transfer_msg(&cfg.denom, cfg.marketing_address.as_ref().unwrap(), marketing_juno)?);
+    }
+
+    let liquidity_juno = new_balance - marketing_juno;
+    if liquidity_juno.is_zero() || pending.token_amount.is_zero() {
+        return Ok(res);
+    }
+
+    // add liquidity to uniswap
+    let liquidity = add_liquidity(deps.storage, deps.querier, env, pending.token_amount, liquidity_juno)?;
+    Ok(res
+        .add_submessages(liquidity.messages)
+        .add_attributes(liquidity.attributes))
 }
 
 
@@ -667,89 +1468,106 @@ fn _get_input_price(
         .map_err(StdError::divide_by_zero)
 }
 
-fn _get_token2_amount_required(
-    token1_amount: Uint128,
-    token2_reserve: Uint128,
-    token1_reserve: Uint128,
-) -> Result<Uint128, StdError> {
-    Ok(token1_amount
-        .checked_mul(token2_reserve)
-        .map_err(StdError::overflow)?
-        .checked_div(token1_reserve)
-        .map_err(StdError::divide_by_zero)?
-        .checked_add(Uint128::new(1))
-        .map_err(StdError::overflow)?)
-}
+/// Sells `token_amount` on the pool. The swap replies to `reply`
 pub fn swap_tokens_for_juno(
     storage: &mut dyn Storage,
     querier: QuerierWrapper,
-    _env: Env,
+    env: Env,
     token_amount: Uint128
-) -> StdResult<Response> {
+) -> Result<Vec<SubMsg>, ContractError> {
     let cfg = CONFIG.load(storage)?;
-    // generate the uniswap pair path of token -> juno
-    //query token1.reserve and token2.reserve
-    let info_response: InfoResponse = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-        contract_addr: cfg.pool_address.clone().into(),
-        msg: to_binary(&WasmswapQueryMsg::Info {})?,
-    }))?;
-    //input token is token2
-    let token_bought = _get_input_price(token_amount, info_response.token2_reserve, info_response.token1_reserve)?;
-    // Juno is Token1, Safemoon is Token2
-    let swap_msg = WasmswapExecuteMsg::Swap {
-        input_token: TokenSelect::Token2,
-        input_amount: token_amount,
-        min_output: token_bought,
-        expiration: None
-    };
-    let callback = CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: cfg.pool_address.clone().into(),
-        msg: to_binary(&swap_msg)?,
-        funds: vec![],
-    });
+    let dex = _primary_dex(storage, &cfg)?;
+    let pool = dex.query_pool(querier, &env, &cfg.pool_address, &cfg.denom)?;
+    let token_bought = _get_input_price(token_amount, pool.token_reserve, pool.denom_reserve)?;
 
-    Ok(Response::new().add_attribute("action", "swap_tokens_for_juno").add_message(callback))
+    let mut msgs = dex.swap_msgs(&env, &pool, token_amount, token_bought, &cfg.swap_limits(&env.block))?;
+    let swap_msg = msgs.pop().ok_or_else(|| StdError::generic_err("No swap message"))?;
+
+    // liquidity is added in `reply` once the swap output is known
+    let mut messages: Vec<SubMsg> = msgs.into_iter().map(SubMsg::new).collect();
+    messages.push(SubMsg::reply_on_success(swap_msg, LIQUIFY_SWAP_REPLY_ID));
+    Ok(messages)
 }
 
+/// Adds as much of `token_amount` and `juno_amount` as the pool ratio allows
 pub fn add_liquidity(
     storage: &mut dyn Storage,
-    _querier: QuerierWrapper,
+    querier: QuerierWrapper,
     env: Env,
     token_amount: Uint128,
     juno_amount: Uint128
-) -> StdResult<Response> {
+) -> Result<Response, ContractError> {
     let cfg = CONFIG.load(storage)?;
 
-    let mut messages:Vec<CosmosMsg> = vec![];
-    let increase_allowance_msg = ExecuteMsg::IncreaseAllowance {
-        spender: cfg.pool_address.clone().into(),
-        amount: token_amount,
-        expires: None 
-    };
-    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: env.contract.address.clone().into(),
-        msg: to_binary(&increase_allowance_msg)?,
-        funds: vec![],
-    }));
-
-    let add_liquidity_msg = WasmswapExecuteMsg::AddLiquidity {
-        token1_amount: juno_amount,
-        max_token2: token_amount,
-        min_liquidity: Uint128::from(1u128),
-        expiration: None
-    };
-    let funds = Coin {
-        amount: juno_amount,
-        denom: cfg.denom,
+    // add liquidity at the reserves left by the swap
+    let dex = _primary_dex(storage, &cfg)?;
+    let pool = dex.query_pool(querier, &env, &cfg.pool_address, &cfg.denom)?;
+    let messages = dex.provide_liquidity_msgs(
+        &env,
+        &pool,
+        &cfg.denom,
+        token_amount,
+        juno_amount,
+        &cfg.swap_limits(&env.block)
+    )?;
+
+    let res = Response::new().add_attribute("action", "add_liquidity");
+    let mut messages = match messages {
+        Some(messages) => messages,
+        None => return Ok(res),
     };
-    messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: cfg.pool_address.clone().into(),
-        msg: to_binary(&add_liquidity_msg)?,
-        funds: vec![funds],
-    }));
+    if cfg.lp_policy.recipient().is_none() {
+        return Ok(res.add_messages(messages));
+    }
 
-    
-    Ok(Response::new().add_attribute("action", "add_liquidity").add_messages(messages))
+    // the minted LP tokens are forwarded in `reply`
+    let lp_token = Addr::unchecked(pool.lp_token_address);
+    let initial_balance = query_denom_balance(querier, &Denom::Cw20(lp_token.clone()), &env.contract.address)?;
+    PENDING_LIQUIDITY.save(storage, &PendingLiquidity { lp_token, initial_balance })?;
+    let provide_msg = messages.pop().ok_or_else(|| StdError::generic_err("No liquidity message"))?;
+    Ok(res
+        .add_messages(messages)
+        .add_submessage(SubMsg::reply_on_success(provide_msg, LIQUIFY_PROVIDE_REPLY_ID)))
+}
+
+/// Forwards the LP tokens minted by `add_liquidity` as the LP policy says
+pub fn reply_liquify_provide(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let pending = PENDING_LIQUIDITY.load(deps.storage)?;
+    PENDING_LIQUIDITY.remove(deps.storage);
+    let cfg = CONFIG.load(deps.storage)?;
+
+    let lp_token = Denom::Cw20(pending.lp_token);
+    let minted = query_denom_balance(deps.querier, &lp_token, &env.contract.address)?
+        .checked_sub(pending.initial_balance)
+        .map_err(StdError::overflow)?;
+
+    let res = Response::new()
+        .add_attribute("action", "forward_lp")
+        .add_attribute("policy", cfg.lp_policy.to_string())
+        .add_attribute("amount", minted);
+    match cfg.lp_policy.recipient() {
+        Some(recipient) if !minted.is_zero() => Ok(res
+            .add_attribute("recipient", recipient)
+            .add_message(denom_transfer_msg(&lp_token, recipient, minted)?)),
+        _ => Ok(res),
+    }
+}
+
+/// Tokens of this contract that swap and liquify may sell
+pub fn _contract_token_balance(storage: &mut dyn Storage, env: &Env) -> StdResult<Uint128> {
+    let cfg = CONFIG.load(storage)?;
+    let contract_addr = env.contract.address.clone();
+    let balance = if ISEXCLUDED.load(storage, contract_addr.clone()).unwrap_or(false) {
+        TOWNED.load(storage, contract_addr).unwrap_or(Uint128::zero())
+    } else {
+        let r_amount = ROWNED.load(storage, contract_addr).unwrap_or(Uint128::zero());
+        _token_from_reflection(storage, r_amount)
+    };
+    // marketing fees wait in the contract until a treasury is set
+    if cfg.marketing_address.is_none() {
+        return Ok(balance.saturating_sub(cfg.marketing_tokens));
+    }
+    Ok(balance)
 }
 
 pub fn _transfer(
@@ -761,70 +1579,55 @@ pub fn _transfer(
     recipient: Addr,
     amount: Uint128,
     in_swap_and_liquify: bool
-) -> Result<Vec<CosmosMsg>, ContractError> {
-    let mut cfg = CONFIG.load(storage)?;
-    if cfg.owner != sender && cfg.owner != recipient && amount > cfg.max_tx_amount {
+) -> Result<Vec<SubMsg>, ContractError> {
+    let cfg = CONFIG.load(storage)?;
+    // the contract's own liquify swaps are capped by max_liquify_amount instead
+    if cfg.owner.as_ref() != Some(&sender) && cfg.owner.as_ref() != Some(&recipient) && sender != env.contract.address && amount > cfg.max_tx_amount {
         return Err(ContractError::MaxTxAmountExceed {});
     }
 
-    let mut msgs: Vec<CosmosMsg> = vec![];
+    let contract_token_balance = _contract_token_balance(storage, &env)?;
 
-    // balance of this contract
-    let mut contract_token_balance;
-    let contract_addr = env.contract.address.clone();
-    if ISEXCLUDED.load(storage, contract_addr.clone()).unwrap_or(false) {
-        contract_token_balance = TOWNED.load(storage, contract_addr.clone()).unwrap_or(Uint128::zero());
-    } else {
-        let r_amount = ROWNED.load(storage, contract_addr.clone()).unwrap_or(Uint128::zero());
-        contract_token_balance = _token_from_reflection(storage, r_amount);
-    }
-    // balance end
-    if contract_token_balance >= cfg.max_tx_amount {
-        contract_token_balance = cfg.max_tx_amount;
-    }
+    let sender_pair = AMM_PAIRS.may_load(storage, sender.clone())?;
+    let recipient_pair = AMM_PAIRS.may_load(storage, recipient.clone())?;
 
     let over_min_token_balance = contract_token_balance >= cfg.num_tokens_sell_to_add_to_liquidity;
-    let mut messages: Vec<CosmosMsg> = vec![];
-    if over_min_token_balance && !in_swap_and_liquify && sender != cfg.pool_address && cfg.swap_and_liquidity_enabled {
-        contract_token_balance = cfg.num_tokens_sell_to_add_to_liquidity;
-        messages = swap_and_liquify(storage, querier, env.clone(), contract_token_balance)?;
+    let mut messages: Vec<SubMsg> = vec![];
+    if over_min_token_balance && !in_swap_and_liquify && sender_pair.is_none() && cfg.swap_and_liquidity_enabled && cfg.denom_side.is_some() {
+        // sell the threshold, whatever has built up above it is left for
+        // the next transfer or a keeper's Liquify
+        messages = swap_and_liquify(storage, querier, env.clone(), cfg.num_tokens_sell_to_add_to_liquidity)?;
     }
     let mut take_fee = true;
     if ISEXCLUDEDFROMFEE.load(storage, recipient.clone()).unwrap_or(false) || ISEXCLUDEDFROMFEE.load(storage, sender.clone()).unwrap_or(false) {
         take_fee = false;
     }
 
-    if !take_fee {
-        if !(cfg.tax_fee == 0u8 && cfg.liquidity_fee == 0u8) {
-            cfg.previous_tax_fee = cfg.tax_fee;
-            cfg.previous_liquidity_fee = cfg.liquidity_fee;
-            
-            cfg.tax_fee = 0u8;
-            cfg.liquidity_fee = 0u8;
-            CONFIG.save(storage, &cfg)?;
-        }
-    }
+    let (kind, pair) = if sender_pair.is_some() {
+        (TransferKind::Buy, sender_pair)
+    } else if recipient_pair.is_some() {
+        (TransferKind::Sell, recipient_pair)
+    } else {
+        (TransferKind::Transfer, None)
+    };
+
+    let fees = FeeContext::new(&cfg, kind, pair.as_ref(), take_fee);
     
     let sender_excluded = ISEXCLUDED.load(storage, sender.clone()).unwrap_or(false);
     let recipient_excluded = ISEXCLUDED.load(storage, recipient.clone()).unwrap_or(false);
     
     if sender_excluded && !recipient_excluded {
-        _transfer_from_excluded(storage, env, sender, recipient, amount)?;
+        _transfer_from_excluded(storage, env, sender, recipient, amount, &fees)?;
     } else if !sender_excluded && recipient_excluded {
-        _transfer_to_excluded(storage, env, sender, recipient, amount)?;
+        _transfer_to_excluded(storage, env, sender, recipient, amount, &fees)?;
     } else if !sender_excluded && !recipient_excluded {
-        _transfer_standard(storage, env, sender, recipient, amount)?;
+        _transfer_standard(storage, env, sender, recipient, amount, &fees)?;
     } else if sender_excluded && recipient_excluded {
-        _transfer_both_excluded(storage, env, sender, recipient, amount)?;
+        _transfer_both_excluded(storage, env, sender, recipient, amount, &fees)?;
     } else {
-        _transfer_standard(storage, env, sender, recipient, amount)?;
+        _transfer_standard(storage, env, sender, recipient, amount, &fees)?;
     }
 
-    if !take_fee {
-        cfg.tax_fee = cfg.previous_tax_fee;
-        cfg.liquidity_fee = cfg.previous_liquidity_fee;
-        CONFIG.save(storage, &cfg)?;
-    }
     // Ok(Response::default())
     Ok(messages)
     // Ok(Response::new().add_attribute("action", "custom_transfer").add_messages(messages))
@@ -835,9 +1638,10 @@ pub fn _transfer_standard(
     env: Env,
     sender: Addr,
     recipient: Addr,
-    t_amount: Uint128
+    t_amount: Uint128,
+    fees: &FeeContext
 ) -> StdResult<Uint128> {
-    let (r_amount, r_transfer_amount, r_fee, t_transfer_amount, t_fee, t_liquidity) = _get_values(storage, t_amount);
+    let (r_amount, r_transfer_amount, r_fee, r_burn, t_transfer_amount, t_fee, t_liquidity, t_burn, t_marketing) = _get_values(storage, t_amount, fees);
     
     let r1 = ROWNED.load(storage, sender.clone()).unwrap_or(Uint128::zero()) - r_amount;
     ROWNED.save(storage, sender.clone(), &r1)?;
@@ -845,8 +1649,10 @@ pub fn _transfer_standard(
     let r2 = ROWNED.load(storage, recipient.clone()).unwrap_or(Uint128::zero()) + r_transfer_amount;
     ROWNED.save(storage, recipient.clone(), &r2)?;
 
-    _take_liquidity(storage, env, t_liquidity)?;
+    _take_liquidity(storage, env.clone(), t_liquidity)?;
+    _take_marketing(storage, env, t_marketing)?;
     _reflect_fee(storage, r_fee, t_fee)?;
+    _burn_fee(storage, r_burn, t_burn)?;
     Ok(t_transfer_amount)
     
 }
@@ -856,9 +1662,10 @@ pub fn _transfer_to_excluded(
     env: Env,
     sender: Addr,
     recipient: Addr,
-    t_amount: Uint128
+    t_amount: Uint128,
+    fees: &FeeContext
 ) -> StdResult<Uint128> {
-    let (r_amount, r_transfer_amount, r_fee, t_transfer_amount, t_fee, t_liquidity) = _get_values(storage, t_amount);
+    let (r_amount, r_transfer_amount, r_fee, r_burn, t_transfer_amount, t_fee, t_liquidity, t_burn, t_marketing) = _get_values(storage, t_amount, fees);
 
     let r1 = ROWNED.load(storage, sender.clone()).unwrap_or(Uint128::zero()) - r_amount;
     ROWNED.save(storage, sender.clone(), &r1)?;
@@ -868,9 +1675,12 @@ pub fn _transfer_to_excluded(
     
     let r2 = ROWNED.load(storage, recipient.clone()).unwrap_or(Uint128::zero()) + r_transfer_amount;
     ROWNED.save(storage, recipient.clone(), &r2)?;
+    _add_excluded_supply(storage, r_transfer_amount, t_transfer_amount)?;
     
-    _take_liquidity(storage, env, t_liquidity)?;
+    _take_liquidity(storage, env.clone(), t_liquidity)?;
+    _take_marketing(storage, env, t_marketing)?;
     _reflect_fee(storage, r_fee, t_fee)?;
+    _burn_fee(storage, r_burn, t_burn)?;
     Ok(t_transfer_amount)
     // _rOwned[sender] = _rOwned[sender].sub(r_amount);
     // _tOwned[recipient] = _tOwned[recipient].add(t_transfer_amount);
@@ -885,22 +1695,26 @@ pub fn _transfer_from_excluded(
     env: Env,
     sender: Addr,
     recipient: Addr,
-    t_amount: Uint128
+    t_amount: Uint128,
+    fees: &FeeContext
 ) -> StdResult<Uint128> {
 
-    let (r_amount, r_transfer_amount, r_fee, t_transfer_amount, t_fee, t_liquidity) = _get_values(storage, t_amount);
+    let (r_amount, r_transfer_amount, r_fee, r_burn, t_transfer_amount, t_fee, t_liquidity, t_burn, t_marketing) = _get_values(storage, t_amount, fees);
     
     let t1 = TOWNED.load(storage, sender.clone()).unwrap_or(Uint128::zero()) - t_amount;
     TOWNED.save(storage, sender.clone(), &t1)?;
     
     let r1 = ROWNED.load(storage, sender.clone()).unwrap_or(Uint128::zero()) - r_amount;
     ROWNED.save(storage, sender.clone(), &r1)?;
+    _sub_excluded_supply(storage, r_amount, t_amount)?;
     
     let r2 = ROWNED.load(storage, recipient.clone()).unwrap_or(Uint128::zero()) + r_transfer_amount;
     ROWNED.save(storage, recipient.clone(), &r2)?;
     
-    _take_liquidity(storage, env, t_liquidity)?;
+    _take_liquidity(storage, env.clone(), t_liquidity)?;
+    _take_marketing(storage, env, t_marketing)?;
     _reflect_fee(storage, r_fee, t_fee)?;
+    _burn_fee(storage, r_burn, t_burn)?;
     Ok(t_transfer_amount)
     // (r_amount, r_transfer_amount, r_fee, t_transfer_amount, t_fee, t_liquidity) = _get_values(t_amount);
     // _tOwned[sender] = _tOwned[sender].sub(t_amount);
@@ -916,24 +1730,29 @@ pub fn _transfer_both_excluded(
     env: Env,
     sender: Addr,
     recipient: Addr,
-    t_amount: Uint128
+    t_amount: Uint128,
+    fees: &FeeContext
 ) -> StdResult<Uint128> {
-    let (r_amount, r_transfer_amount, r_fee, t_transfer_amount, t_fee, t_liquidity) = _get_values(storage, t_amount);
+    let (r_amount, r_transfer_amount, r_fee, r_burn, t_transfer_amount, t_fee, t_liquidity, t_burn, t_marketing) = _get_values(storage, t_amount, fees);
     
     let t1 = TOWNED.load(storage, sender.clone()).unwrap_or(Uint128::zero()) - t_amount;
     TOWNED.save(storage, sender.clone(), &t1)?;
     
     let r1 = ROWNED.load(storage, sender.clone()).unwrap_or(Uint128::zero()) - r_amount;
     ROWNED.save(storage, sender.clone(), &r1)?;
+    _sub_excluded_supply(storage, r_amount, t_amount)?;
     
     let t2 = TOWNED.load(storage, recipient.clone()).unwrap_or(Uint128::zero()) + t_transfer_amount;
     TOWNED.save(storage, recipient.clone(), &t2)?;
     
     let r2 = ROWNED.load(storage, recipient.clone()).unwrap_or(Uint128::zero()) + r_transfer_amount;
     ROWNED.save(storage, recipient.clone(), &r2)?;
+    _add_excluded_supply(storage, r_transfer_amount, t_transfer_amount)?;
     
-    _take_liquidity(storage, env, t_liquidity)?;
+    _take_liquidity(storage, env.clone(), t_liquidity)?;
+    _take_marketing(storage, env, t_marketing)?;
     _reflect_fee(storage, r_fee, t_fee)?;
+    _burn_fee(storage, r_burn, t_burn)?;
     Ok(t_transfer_amount)
     // (r_amount, r_transfer_amount, r_fee, t_transfer_amount, t_fee, t_liquidity) = _get_values(t_amount);
     // _tOwned[sender] = _tOwned[sender].sub(t_amount);
@@ -960,7 +1779,7 @@ pub fn execute_custom_transfer(
     let msgs = _transfer(deps.api, deps.storage, deps.querier, env, info.sender.clone(), rcpt_addr.clone(), amount, false)?;
     
     let res = Response::new()
-        .add_messages(msgs)
+        .add_submessages(msgs)
         .add_attribute("action", "transfer")
         .add_attribute("from", info.sender)
         .add_attribute("to", recipient)
@@ -984,9 +1803,12 @@ pub fn execute_custom_transfer_from(
     }
     // deduct allowance before doing anything else have enough allowance
     deduct_allowance(deps.storage, &owner_addr, &info.sender, &env.block, amount)?;
-    _transfer(deps.api, deps.storage, deps.querier, env, owner_addr.clone(), rcpt_addr.clone(), amount, false)?;
+    // the pool pulls the contract's own tokens with TransferFrom while
+    // swapping and adding liquidity, which must not liquify again
+    let flag = owner_addr == env.contract.address;
+    let msgs = _transfer(deps.api, deps.storage, deps.querier, env, owner_addr.clone(), rcpt_addr.clone(), amount, flag)?;
     
-    let res = Response::new().add_attributes(vec![
+    let res = Response::new().add_submessages(msgs).add_attributes(vec![
         attr("action", "transfer_from"),
         attr("from", owner),
         attr("to", recipient),
@@ -1011,17 +1833,17 @@ pub fn execute_custom_send(
 
     let rcpt_addr = deps.api.addr_validate(&contract)?;
 
-    let cfg = CONFIG.load(deps.storage)?;
     let flag;
-    if rcpt_addr == cfg.pool_address {
+    if AMM_PAIRS.has(deps.storage, rcpt_addr.clone()) {
         flag = true;
     } else {
         flag = false;
     }
-    _transfer(deps.api, deps.storage, deps.querier, env, info.sender.clone(), rcpt_addr.clone(), amount, flag)?;
+    let msgs = _transfer(deps.api, deps.storage, deps.querier, env, info.sender.clone(), rcpt_addr.clone(), amount, flag)?;
     // move the tokens to the contract
     
     let res = Response::new()
+        .add_submessages(msgs)
         .add_attribute("action", "send")
         .add_attribute("from", &info.sender)
         .add_attribute("to", &contract)
@@ -1049,18 +1871,20 @@ pub fn execute_custom_send_from(
 ) -> Result<Response, ContractError> {
     let rcpt_addr = deps.api.addr_validate(&contract)?;
     let owner_addr = deps.api.addr_validate(&owner)?;
+    if amount == Uint128::zero() {
+        return Err(ContractError::InvalidZeroAmount {});
+    }
 
     // deduct allowance before doing anything else have enough allowance
     deduct_allowance(deps.storage, &owner_addr, &info.sender, &env.block, amount)?;
 
-    let cfg = CONFIG.load(deps.storage)?;
     let flag;
-    if rcpt_addr == cfg.pool_address {
+    if AMM_PAIRS.has(deps.storage, rcpt_addr.clone()) || owner_addr == env.contract.address {
         flag = true;
     } else {
         flag = false;
     }
-    _transfer(deps.api, deps.storage, deps.querier, env, owner_addr.clone(), rcpt_addr.clone(), amount, flag)?;
+    let msgs = _transfer(deps.api, deps.storage, deps.querier, env, owner_addr.clone(), rcpt_addr.clone(), amount, flag)?;
     // move the tokens to the contract
 
     let attrs = vec![
@@ -1079,54 +1903,16 @@ pub fn execute_custom_send_from(
     }
     .into_cosmos_msg(contract)?;
 
-    let res = Response::new().add_message(msg).add_attributes(attrs);
+    let res = Response::new().add_submessages(msgs).add_message(msg).add_attributes(attrs);
     Ok(res)
 }
-fn map_excluded(
-    item: StdResult<(Addr, bool)>,
-) -> StdResult<ExcludedInfo> {
-    item.map(|(addr, excluded)| {
-        ExcludedInfo {
-            addr,
-            excluded
-        }
-    })
-}
 pub fn _get_rate(storage: &mut dyn Storage) -> Uint128 {
     let (r_supply, t_supply) = _get_current_supply(storage);
     return r_supply / t_supply;
 }
 
 pub fn _get_current_supply(storage: &mut dyn Storage) -> (Uint128, Uint128) {
-    let cfg = CONFIG.load(storage).unwrap();
-    let mut r_supply = cfg.r_total;
-    let mut t_supply = cfg.t_total;
-
-    let excluded_list:StdResult<Vec<_>> = ISEXCLUDED
-        .range(storage, None, None, Order::Ascending)
-        .map(|item| map_excluded(item))
-        .collect();
-    
-    let mut r_val;
-    let mut t_val;
-    for item in excluded_list.unwrap() {
-        if !item.excluded {
-            continue;
-        }
-        r_val = ROWNED.load(storage, item.addr.clone()).unwrap_or(Uint128::zero());
-        t_val = TOWNED.load(storage, item.addr.clone()).unwrap_or(Uint128::zero());
-        if  r_val > r_supply ||  t_val > t_supply {
-            return (cfg.r_total, cfg.t_total);
-        }
-        r_supply = r_supply - r_val;
-        t_supply = t_supply - t_val;
-        
-    }
-    if r_supply < cfg.r_total / cfg.t_total {
-        return (cfg.r_total, cfg.t_total);
-    }
-    return (r_supply, t_supply);
-    
+    _get_current_supply_immut(storage)
 }
 
 
@@ -1140,12 +1926,45 @@ pub fn _token_from_reflection(storage: &mut dyn Storage, r_amount:Uint128) -> Ui
 }
 
 
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn migrate(deps: DepsMut, _env: Env, _msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::CannotMigrate { previous_contract: stored.contract });
+    }
+
+    let stored_version: Version = stored.version.parse()?;
+    let new_version: Version = CONTRACT_VERSION.parse()?;
+    if stored_version > new_version {
+        return Err(ContractError::CannotMigrateVersion { previous_version: stored.version });
+    }
+
+    // state upgrades, oldest first
+    if stored_version < Version::new(0, 13, 0) {
+        migrate_config_v0_12(deps.storage)?;
+    }
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("from_version", stored.version)
+        .add_attribute("to_version", CONTRACT_VERSION))
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         
         // inherited from cw20-base
         QueryMsg::Config {} => to_binary(&query_config(deps)?),
+        QueryMsg::OwnershipProposal {} => to_json_binary(&query_ownership_proposal(deps)?),
+        QueryMsg::Roles { address } => to_json_binary(&query_roles(deps, address)?),
+        QueryMsg::PendingChanges {} => to_json_binary(&query_pending_changes(deps)?),
+        QueryMsg::AmmPairs {} => to_json_binary(&query_amm_pairs(deps)?),
+        QueryMsg::Withdrawals { start_after, limit } => {
+            to_json_binary(&query_withdrawals(deps, start_after, limit)?)
+        },
         QueryMsg::TokenInfo {} => to_binary(&custom_query_token_info(deps)?),
         QueryMsg::Balance { address } => to_binary(&custom_query_balance(deps, address)?),
         QueryMsg::Allowance { owner, spender } => {
@@ -1157,6 +1976,9 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
         QueryMsg::TotalFees { } => {
             to_binary(&query_total_fees(deps)?)
         },
+        QueryMsg::TotalBurned { } => {
+            to_json_binary(&query_total_burned(deps)?)
+        },
         QueryMsg::ReflectionFromToken { t_amount, deduct_transfer_fee } => {
             to_binary(&query_reflection_from_token(deps, t_amount, deduct_transfer_fee)?)
         },
@@ -1172,21 +1994,81 @@ pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
 
 pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
     let cfg = CONFIG.load(deps.storage)?;
+    let dex = _primary_dex(deps.storage, &cfg)?;
     Ok(ConfigResponse {
+        frozen: cfg.owner.is_none(),
         owner: cfg.owner,
         t_total: cfg.t_total,
         r_total: cfg.r_total,
         t_fee_total: cfg.t_fee_total,
-        tax_fee: cfg.tax_fee,
-        previous_tax_fee: cfg.previous_tax_fee,
-        liquidity_fee: cfg.liquidity_fee,
-        previous_liquidity_fee: cfg.previous_liquidity_fee,
+        t_burn_total: cfg.t_burn_total,
+        buy_fee: cfg.buy_fee,
+        sell_fee: cfg.sell_fee,
+        transfer_fee: cfg.transfer_fee,
+        marketing_fee: cfg.marketing_fee,
+        marketing_address: cfg.marketing_address,
+        marketing_tokens: cfg.marketing_tokens,
+        max_total_fee: cfg.max_total_fee,
         pool_address: cfg.pool_address,
+        dex,
+        denom: cfg.denom,
+        denom_side: cfg.denom_side,
         swap_and_liquidity_enabled: cfg.swap_and_liquidity_enabled,
         max_tx_amount: cfg.max_tx_amount,
-        num_tokens_sell_to_add_to_liquidity: cfg.num_tokens_sell_to_add_to_liquidity
+        num_tokens_sell_to_add_to_liquidity: cfg.num_tokens_sell_to_add_to_liquidity,
+        max_liquify_amount: cfg.max_liquify_amount,
+        timelock: cfg.timelock,
+        liquify_interval: cfg.liquify_interval,
+        max_slippage_bps: cfg.max_slippage_bps,
+        swap_expiration: cfg.swap_expiration,
+        lp_policy: cfg.lp_policy,
+        withdraw_caps: cfg.withdraw_caps
+    })
+}
+pub fn query_ownership_proposal(deps: Deps) -> StdResult<OwnershipProposalResponse> {
+    let proposal = OWNERSHIP_PROPOSAL.may_load(deps.storage)?;
+    Ok(OwnershipProposalResponse {
+        owner: proposal.clone().map(|p| p.owner),
+        expiry: proposal.and_then(|p| p.expiry),
     })
 }
+
+pub fn query_roles(deps: Deps, address: String) -> StdResult<RolesResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let roles = ROLES.may_load(deps.storage, address)?.unwrap_or_default();
+    Ok(RolesResponse { roles })
+}
+
+pub fn query_pending_changes(deps: Deps) -> StdResult<PendingChangesResponse> {
+    let changes = PENDING_CHANGES
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, pending)| pending))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(PendingChangesResponse { changes })
+}
+
+pub fn query_amm_pairs(deps: Deps) -> StdResult<AmmPairsResponse> {
+    let pairs = AMM_PAIRS
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(address, pair)| AmmPairInfo { address, pair }))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(AmmPairsResponse { pairs })
+}
+
+const DEFAULT_WITHDRAWALS_LIMIT: u32 = 10;
+const MAX_WITHDRAWALS_LIMIT: u32 = 30;
+
+pub fn query_withdrawals(deps: Deps, start_after: Option<u64>, limit: Option<u32>) -> StdResult<WithdrawalsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_WITHDRAWALS_LIMIT).min(MAX_WITHDRAWALS_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let withdrawals = WITHDRAWALS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, withdrawal)| withdrawal))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(WithdrawalsResponse { withdrawals })
+}
+
 pub fn custom_query_token_info(deps: Deps) -> StdResult<TokenInfoResponse> {
     let info = TOKEN_INFO.load(deps.storage)?;
     let cfg = CONFIG.load(deps.storage)?;
@@ -1227,33 +2109,35 @@ pub fn query_total_fees(deps: Deps) -> StdResult<Uint128Response> {
     Ok(Uint128Response { ret })
 }
 
+pub fn query_total_burned(deps: Deps) -> StdResult<Uint128Response> {
+    let cfg = CONFIG.load(deps.storage)?;
+    let ret = cfg.t_burn_total;
+    Ok(Uint128Response { ret })
+}
+
 pub fn query_reflection_from_token(deps: Deps, t_amount: Uint128, deduct_transfer_fee: bool) -> StdResult<Uint128Response> {
-    // let mut ret = Uint128::zero();
-    
-    // let cfg = CONFIG.load(deps.storage)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    if t_amount > cfg.t_total {
+        return Err(StdError::generic_err("Amount must be less than supply"));
+    }
 
-    // // if (t_amount <= cfg.t_total) {
-    // //     if !deduct_transfer_fee {
-    // //         let (r_amount, r_transfer_amount, r_fee, t_transfer_amount, t_fee, t_liquidity) = _get_values(deps.storage , t_amount);
-    // //     }
-    // // }
-    // let ret = cfg.t_fee_total;
-    Ok(Uint128Response { ret: Uint128::zero() })
+    let (r_amount, r_transfer_amount, _r_fee, _r_burn, _t_transfer_amount, _t_fee, _t_liquidity, _t_burn, _t_marketing) = _get_values_immut(deps.storage, t_amount, &FeeContext::new(&cfg, TransferKind::Transfer, None, true));
+    if !deduct_transfer_fee {
+        Ok(Uint128Response { ret: r_amount })
+    } else {
+        Ok(Uint128Response { ret: r_transfer_amount })
+    }
 }
 
 
 pub fn query_token_from_reflection(deps: Deps, r_amount: Uint128) -> StdResult<Uint128Response> {
-    // let mut ret = Uint128::zero();
-    
-    // let cfg = CONFIG.load(deps.storage)?;
+    let cfg = CONFIG.load(deps.storage)?;
+    if r_amount > cfg.r_total {
+        return Err(StdError::generic_err("Amount must be less than total reflections"));
+    }
 
-    // // if (t_amount <= cfg.t_total) {
-    // //     if !deduct_transfer_fee {
-    // //         let (r_amount, r_transfer_amount, r_fee, t_transfer_amount, t_fee, t_liquidity) = _get_values(deps.storage , t_amount);
-    // //     }
-    // // }
-    // let ret = cfg.t_fee_total;
-    Ok(Uint128Response { ret: Uint128::zero() })
+    let current_rate = _get_rate_immut(deps.storage);
+    Ok(Uint128Response { ret: r_amount.checked_div(current_rate)? })
 }
 
 pub fn _token_from_reflection_immut(storage: &dyn Storage, r_amount:Uint128) -> Uint128 {
@@ -1265,6 +2149,13 @@ pub fn _token_from_reflection_immut(storage: &dyn Storage, r_amount:Uint128) ->
     return r_amount.checked_div(current_rate).unwrap();
 }
 
+pub fn _get_values_immut(storage: &dyn Storage, t_amount: Uint128, fees: &FeeContext) -> (Uint128, Uint128, Uint128, Uint128, Uint128, Uint128, Uint128, Uint128, Uint128) {
+    let (t_transfer_amount, t_fee, t_liquidity, t_burn, t_marketing) = _get_t_values(t_amount, fees);
+    let current_rate = _get_rate_immut(storage);
+    let (r_amount, r_transfer_amount, r_fee, r_burn) = _get_r_values( t_amount, t_fee, t_liquidity, t_burn, t_marketing, current_rate);
+    return (r_amount, r_transfer_amount, r_fee, r_burn, t_transfer_amount, t_fee, t_liquidity, t_burn, t_marketing);
+}
+
 pub fn _get_rate_immut(storage: &dyn Storage) -> Uint128 {
     let (r_supply, t_supply) = _get_current_supply_immut(storage);
     return r_supply / t_supply;
@@ -1273,32 +2164,17 @@ pub fn _get_rate_immut(storage: &dyn Storage) -> Uint128 {
 
 pub fn _get_current_supply_immut(storage: &dyn Storage) -> (Uint128, Uint128) {
     let cfg = CONFIG.load(storage).unwrap();
-    let mut r_supply = cfg.r_total;
-    let mut t_supply = cfg.t_total;
 
-    let excluded_list:StdResult<Vec<_>> = ISEXCLUDED
-        .range(storage, None, None, Order::Ascending)
-        .map(|item| map_excluded(item))
-        .collect();
-    
-    let mut r_val;
-    let mut t_val;
-    for item in excluded_list.unwrap() {
-        if !item.excluded {
-            continue;
-        }
-        r_val = ROWNED.load(storage, item.addr.clone()).unwrap_or(Uint128::zero());
-        t_val = TOWNED.load(storage, item.addr.clone()).unwrap_or(Uint128::zero());
-        if  r_val > r_supply ||  t_val > t_supply {
-            return (cfg.r_total, cfg.t_total);
-        }
-        r_supply = r_supply - r_val;
-        t_supply = t_supply - t_val;
-        
+    // excluded balances are tracked as running totals, see `_add_excluded_supply`
+    if cfg.excluded_r_total > cfg.r_total || cfg.excluded_t_total > cfg.t_total {
+        return (cfg.r_total, cfg.t_total);
     }
-    if r_supply < cfg.r_total / cfg.t_total {
+    let r_supply = cfg.r_total - cfg.excluded_r_total;
+    let t_supply = cfg.t_total - cfg.excluded_t_total;
+
+    if r_supply < cfg.r_total / cfg.t_total || t_supply.is_zero() {
         return (cfg.r_total, cfg.t_total);
     }
     return (r_supply, t_supply);
     
-}
\ No newline at end of file
+}
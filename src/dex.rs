@@ -0,0 +1,384 @@
+use std::fmt;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{
+    to_json_binary, Addr, BalanceResponse, BankMsg, BankQuery, Coin, CosmosMsg, Decimal, Env,
+    QuerierWrapper, QueryRequest, StdError, StdResult, Uint128, WasmMsg, WasmQuery,
+};
+use cw20::{BalanceResponse as Cw20BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, Denom, Expiration};
+
+use crate::astroport::{self, Asset, AssetInfo, MAX_SLIPPAGE_TOLERANCE_BPS};
+use crate::error::ContractError;
+use crate::msg::ExecuteMsg;
+use crate::state::BPS_DENOMINATOR;
+use crate::wasmswap::{self, opposite, TokenSelect};
+
+/// Kind of pool contract swap and liquify talks to
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Dex {
+    Wasmswap,
+    /// Astroport and Terraswap style pairs
+    Astroport,
+}
+
+impl fmt::Display for Dex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Dex::Wasmswap => write!(f, "wasmswap"),
+            Dex::Astroport => write!(f, "astroport"),
+        }
+    }
+}
+
+/// A pool of this token and the quote denom
+#[derive(Clone, Debug, PartialEq)]
+pub struct PoolInfo {
+    pub address: Addr,
+    /// Side of the pool holding the quote denom, token1 being the first asset
+    pub denom_side: TokenSelect,
+    pub denom_reserve: Uint128,
+    pub token_reserve: Uint128,
+    pub lp_token_supply: Uint128,
+    pub lp_token_address: String,
+}
+
+/// How far swap and liquify may stray from the expected amounts
+#[derive(Clone, Debug, PartialEq)]
+pub struct SwapLimits {
+    pub max_slippage_bps: u16,
+    /// Deadline of the swap, ignored by Astroport pairs
+    pub expiration: Option<Expiration>,
+}
+
+impl Dex {
+    /// Queries `pool`, failing unless it pairs `denom` with this token
+    pub fn query_pool(
+        &self,
+        querier: QuerierWrapper,
+        env: &Env,
+        pool: &Addr,
+        denom: &Denom,
+    ) -> Result<PoolInfo, ContractError> {
+        let (first, second, lp_token_supply, lp_token_address) = match self {
+            Dex::Wasmswap => {
+                let info: wasmswap::InfoResponse = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+                    contract_addr: pool.clone().into(),
+                    msg: to_json_binary(&wasmswap::QueryMsg::Info {})?,
+                }))?;
+                (
+                    (info.token1_denom, info.token1_reserve),
+                    (info.token2_denom, info.token2_reserve),
+                    info.lp_token_supply,
+                    info.lp_token_address,
+                )
+            }
+            Dex::Astroport => {
+                let pair: astroport::PairInfo = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+                    contract_addr: pool.clone().into(),
+                    msg: to_json_binary(&astroport::QueryMsg::Pair {})?,
+                }))?;
+                let pool_response: astroport::PoolResponse = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+                    contract_addr: pool.clone().into(),
+                    msg: to_json_binary(&astroport::QueryMsg::Pool {})?,
+                }))?;
+                let [first, second] = pool_response.assets;
+                (
+                    (first.info.into(), first.amount),
+                    (second.info.into(), second.amount),
+                    pool_response.total_share,
+                    pair.liquidity_token,
+                )
+            }
+        };
+
+        let is_denom = |asset: &Denom| asset == denom;
+        let is_token = |asset: &Denom| matches!(asset, Denom::Cw20(token) if token == &env.contract.address);
+        let (denom_side, denom_reserve, token_reserve) = if is_denom(&first.0) && is_token(&second.0) {
+            (TokenSelect::Token1, first.1, second.1)
+        } else if is_token(&first.0) && is_denom(&second.0) {
+            (TokenSelect::Token2, second.1, first.1)
+        } else {
+            return Err(ContractError::InvalidPool { pool: pool.to_string() });
+        };
+
+        Ok(PoolInfo {
+            address: pool.clone(),
+            denom_side,
+            denom_reserve,
+            token_reserve,
+            lp_token_supply,
+            lp_token_address,
+        })
+    }
+
+    /// Messages selling `token_amount` of this token for the quote denom,
+    /// accepting up to the limits' slippage less than `expected_output`.
+    /// The swap is the last message
+    pub fn swap_msgs(
+        &self,
+        env: &Env,
+        info: &PoolInfo,
+        token_amount: Uint128,
+        expected_output: Uint128,
+        limits: &SwapLimits,
+    ) -> StdResult<Vec<CosmosMsg>> {
+        let pool = &info.address;
+        match self {
+            Dex::Wasmswap => {
+                let increase_allowance_msg = ExecuteMsg::IncreaseAllowance {
+                    spender: pool.clone().into(),
+                    amount: token_amount,
+                    expires: None,
+                };
+                let swap_msg = wasmswap::ExecuteMsg::Swap {
+                    input_token: opposite(&info.denom_side),
+                    input_amount: token_amount,
+                    min_output: apply_slippage(expected_output, limits.max_slippage_bps),
+                    expiration: limits.expiration,
+                };
+                Ok(vec![
+                    _execute_self(env, &increase_allowance_msg)?,
+                    CosmosMsg::Wasm(WasmMsg::Execute {
+                        contract_addr: pool.clone().into(),
+                        msg: to_json_binary(&swap_msg)?,
+                        funds: vec![],
+                    }),
+                ])
+            }
+            Dex::Astroport => {
+                // the pair checks the return against the price implied by
+                // belief_price, give or take max_spread
+                let belief_price = if expected_output.is_zero() {
+                    None
+                } else {
+                    Some(Decimal::from_ratio(token_amount, expected_output))
+                };
+                let hook_msg = astroport::Cw20HookMsg::Swap {
+                    belief_price,
+                    max_spread: Some(Decimal::from_ratio(limits.max_slippage_bps, BPS_DENOMINATOR)),
+                    to: None,
+                };
+                let send_msg = ExecuteMsg::Send {
+                    contract: pool.clone().into(),
+                    amount: token_amount,
+                    msg: to_json_binary(&hook_msg)?,
+                };
+                Ok(vec![_execute_self(env, &send_msg)?])
+            }
+        }
+    }
+
+    /// Messages adding as much of `token_available` and `denom_available` as
+    /// the pool's current ratio allows. None when too little is available to
+    /// pair
+    pub fn provide_liquidity_msgs(
+        &self,
+        env: &Env,
+        info: &PoolInfo,
+        denom: &Denom,
+        token_available: Uint128,
+        denom_available: Uint128,
+        limits: &SwapLimits,
+    ) -> StdResult<Option<Vec<CosmosMsg>>> {
+        if token_available.is_zero() || denom_available.is_zero() {
+            return Ok(None);
+        }
+        let pool = &info.address;
+
+        match self {
+            Dex::Wasmswap => {
+                let (token1_reserve, token2_reserve) = match info.denom_side {
+                    TokenSelect::Token1 => (info.denom_reserve, info.token_reserve),
+                    TokenSelect::Token2 => (info.token_reserve, info.denom_reserve),
+                };
+                let (token1_available, token2_available) = match info.denom_side {
+                    TokenSelect::Token1 => (denom_available, token_available),
+                    TokenSelect::Token2 => (token_available, denom_available),
+                };
+                let mut token1_amount = token1_available;
+                let mut token2_amount = _get_token2_amount_required(token1_amount, token2_reserve, token1_reserve)?;
+                // token2 cannot match all of token1, pair as much as it can
+                if token2_amount > token2_available {
+                    token1_amount = (token2_available - Uint128::new(1))
+                        .multiply_ratio(token1_reserve, token2_reserve);
+                    if token1_amount.is_zero() {
+                        return Ok(None);
+                    }
+                    token2_amount = _get_token2_amount_required(token1_amount, token2_reserve, token1_reserve)?;
+                }
+
+                // the pool mints LP tokens pro rata to the token1 added
+                let expected_liquidity = if info.lp_token_supply.is_zero() {
+                    token1_amount
+                } else {
+                    token1_amount.multiply_ratio(info.lp_token_supply, token1_reserve)
+                };
+
+                // a native token2 is sent in full as max_token2, the pool
+                // refunds the rest
+                let (token_amount, denom_amount) = match info.denom_side {
+                    TokenSelect::Token1 => (token2_amount, token1_amount),
+                    TokenSelect::Token2 => (token1_amount, token2_amount),
+                };
+                let increase_allowance_msg = ExecuteMsg::IncreaseAllowance {
+                    spender: pool.clone().into(),
+                    amount: token_amount,
+                    expires: None,
+                };
+                let add_liquidity_msg = wasmswap::ExecuteMsg::AddLiquidity {
+                    token1_amount,
+                    min_liquidity: apply_slippage(expected_liquidity, limits.max_slippage_bps),
+                    max_token2: token2_amount,
+                    expiration: limits.expiration,
+                };
+                let mut messages = vec![_execute_self(env, &increase_allowance_msg)?];
+                messages.extend(_denom_allowance_msg(denom, pool, denom_amount)?);
+                messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: pool.clone().into(),
+                    msg: to_json_binary(&add_liquidity_msg)?,
+                    funds: _denom_funds(denom, denom_amount),
+                }));
+                Ok(Some(messages))
+            }
+            Dex::Astroport => {
+                if info.denom_reserve.is_zero() || info.token_reserve.is_zero() {
+                    return Err(StdError::generic_err("No liquidity"));
+                }
+                // the pair keeps whatever exceeds the pool ratio
+                let mut denom_amount = denom_available;
+                let mut token_amount = denom_amount.multiply_ratio(info.token_reserve, info.denom_reserve);
+                if token_amount > token_available {
+                    token_amount = token_available;
+                    denom_amount = token_amount.multiply_ratio(info.denom_reserve, info.token_reserve);
+                }
+                if token_amount.is_zero() || denom_amount.is_zero() {
+                    return Ok(None);
+                }
+
+                let token_asset = Asset {
+                    info: AssetInfo::Token { contract_addr: env.contract.address.clone() },
+                    amount: token_amount,
+                };
+                let denom_asset = Asset {
+                    info: AssetInfo::from(denom.clone()),
+                    amount: denom_amount,
+                };
+                let assets = match info.denom_side {
+                    TokenSelect::Token1 => [denom_asset, token_asset],
+                    TokenSelect::Token2 => [token_asset, denom_asset],
+                };
+
+                let increase_allowance_msg = ExecuteMsg::IncreaseAllowance {
+                    spender: pool.clone().into(),
+                    amount: token_amount,
+                    expires: None,
+                };
+                let provide_liquidity_msg = astroport::ExecuteMsg::ProvideLiquidity {
+                    assets,
+                    slippage_tolerance: Some(Decimal::from_ratio(
+                        limits.max_slippage_bps.min(MAX_SLIPPAGE_TOLERANCE_BPS),
+                        BPS_DENOMINATOR,
+                    )),
+                    receiver: None,
+                };
+                let mut messages = vec![_execute_self(env, &increase_allowance_msg)?];
+                messages.extend(_denom_allowance_msg(denom, pool, denom_amount)?);
+                messages.push(CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: pool.clone().into(),
+                    msg: to_json_binary(&provide_liquidity_msg)?,
+                    funds: _denom_funds(denom, denom_amount),
+                }));
+                Ok(Some(messages))
+            }
+        }
+    }
+}
+
+/// Lowest acceptable outcome when `expected` may slip by up to `max_slippage_bps`
+pub fn apply_slippage(expected: Uint128, max_slippage_bps: u16) -> Uint128 {
+    expected.multiply_ratio(BPS_DENOMINATOR - max_slippage_bps, BPS_DENOMINATOR)
+}
+
+/// Balance of `address` in `denom`
+pub fn query_denom_balance(querier: QuerierWrapper, denom: &Denom, address: &Addr) -> StdResult<Uint128> {
+    match denom {
+        Denom::Native(denom) => {
+            let balance: BalanceResponse = querier.query(&QueryRequest::Bank(BankQuery::Balance {
+                address: address.clone().into(),
+                denom: denom.clone(),
+            }))?;
+            Ok(balance.amount.amount)
+        }
+        Denom::Cw20(token) => {
+            let balance: Cw20BalanceResponse = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+                contract_addr: token.clone().into(),
+                msg: to_json_binary(&Cw20QueryMsg::Balance { address: address.clone().into() })?,
+            }))?;
+            Ok(balance.balance)
+        }
+    }
+}
+
+/// Sends `amount` of `denom` from this contract to `recipient`
+pub fn denom_transfer_msg(denom: &Denom, recipient: &Addr, amount: Uint128) -> StdResult<CosmosMsg> {
+    match denom {
+        Denom::Native(denom) => Ok(CosmosMsg::Bank(BankMsg::Send {
+            to_address: recipient.clone().into(),
+            amount: vec![Coin { amount, denom: denom.clone() }],
+        })),
+        Denom::Cw20(token) => Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: token.clone().into(),
+            msg: to_json_binary(&Cw20ExecuteMsg::Transfer { recipient: recipient.clone().into(), amount })?,
+            funds: vec![],
+        })),
+    }
+}
+
+/// Native quote amounts are attached to the pool message
+fn _denom_funds(denom: &Denom, amount: Uint128) -> Vec<Coin> {
+    match denom {
+        Denom::Native(denom) => vec![Coin { amount, denom: denom.clone() }],
+        Denom::Cw20(_) => vec![],
+    }
+}
+
+/// cw20 quote amounts are pulled by the pool and need an allowance
+fn _denom_allowance_msg(denom: &Denom, spender: &Addr, amount: Uint128) -> StdResult<Option<CosmosMsg>> {
+    match denom {
+        Denom::Native(_) => Ok(None),
+        Denom::Cw20(token) => Ok(Some(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: token.clone().into(),
+            msg: to_json_binary(&Cw20ExecuteMsg::IncreaseAllowance {
+                spender: spender.clone().into(),
+                amount,
+                expires: None,
+            })?,
+            funds: vec![],
+        }))),
+    }
+}
+
+fn _execute_self(env: &Env, msg: &ExecuteMsg) -> StdResult<CosmosMsg> {
+    Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: env.contract.address.clone().into(),
+        msg: to_json_binary(msg)?,
+        funds: vec![],
+    }))
+}
+
+fn _get_token2_amount_required(
+    token1_amount: Uint128,
+    token2_reserve: Uint128,
+    token1_reserve: Uint128,
+) -> Result<Uint128, StdError> {
+    token1_amount
+        .checked_mul(token2_reserve)
+        .map_err(StdError::overflow)?
+        .checked_div(token1_reserve)
+        .map_err(StdError::divide_by_zero)?
+        .checked_add(Uint128::new(1))
+        .map_err(StdError::overflow)
+}
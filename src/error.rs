@@ -1,4 +1,5 @@
 use cosmwasm_std::{StdError, Uint128};
+use cw_utils::Expiration;
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -9,6 +10,12 @@ pub enum ContractError {
     #[error("Unauthorized")]
     Unauthorized {},
 
+    #[error("Unknown reply id: {id}")]
+    UnknownReplyId { id: u64 },
+
+    #[error("Contract is frozen")]
+    Frozen {},
+
     #[error("Validator '{validator}' not in current validator set")]
     NotInValidatorSet { validator: String },
 
@@ -36,6 +43,42 @@ pub enum ContractError {
     #[error("Invalid zero amount")]
     InvalidZeroAmount {},
 
+    #[error("{pool} is not a pool of this token and the quote denom")]
+    InvalidPool { pool: String },
+
+    #[error("The primary pool cannot be removed")]
+    CannotRemovePrimaryPool {},
+
+    #[error("Swap and liquify is disabled")]
+    SwapAndLiquifyDisabled {},
+
+    #[error("Cannot liquify before {next_liquify}")]
+    LiquifyCooldown { next_liquify: Expiration },
+
+    #[error("Liquify amount cannot exceed the total supply")]
+    LiquifyAmountTooHigh {},
+
+    #[error("Max liquify amount cannot be below the liquify threshold")]
+    LiquifyCapBelowThreshold {},
+
+    #[error("Slippage cannot exceed 100%")]
+    SlippageTooHigh {},
+
+    #[error("LP tokens are locked until {until}")]
+    LpLocked { until: Expiration },
+
+    #[error("LP tokens cannot be withdrawn under the {policy} policy")]
+    LpNotWithdrawable { policy: String },
+
+    #[error("Cannot withdraw more than the {available} held by the contract")]
+    WithdrawAmountTooHigh { available: Uint128 },
+
+    #[error("Withdrawal exceeds the cap for the period, {available} left")]
+    WithdrawCapExceeded { available: Uint128 },
+
+    #[error("Combined fee exceeds the maximum")]
+    FeeTooHigh {},
+
     #[error("Transfer amount exceeds the maxTxAmount")]
     MaxTxAmountExceed {},
 
@@ -45,6 +88,18 @@ pub enum ContractError {
     #[error("Account is already excluded")]
     AccountAlreadyExcluded {},
 
+    #[error("Account is not excluded")]
+    AccountNotExcluded {},
+
+    #[error("No ownership proposal")]
+    NoOwnershipProposal {},
+
+    #[error("Ownership proposal is expired")]
+    OwnershipProposalExpired {},
+
+    #[error("Change is timelocked until {executable_at}")]
+    Timelocked { executable_at: Expiration },
+
     #[error("Allowance is expired")]
     Expired {},
 
@@ -56,6 +111,21 @@ pub enum ContractError {
 
     #[error("Duplicate initial balance addresses")]
     DuplicateInitialBalanceAddresses {},
+
+    #[error("Cannot migrate from different contract type: {previous_contract}")]
+    CannotMigrate { previous_contract: String },
+
+    #[error("Cannot migrate from newer version: {previous_version}")]
+    CannotMigrateVersion { previous_version: String },
+
+    #[error("Semver parsing error: {0}")]
+    SemVer(String),
+}
+
+impl From<semver::Error> for ContractError {
+    fn from(err: semver::Error) -> Self {
+        ContractError::SemVer(err.to_string())
+    }
 }
 
 impl From<cw20_base::ContractError> for ContractError {
@@ -1,6 +1,15 @@
+pub mod astroport;
 pub mod contract;
+pub mod dex;
 mod error;
+pub mod migrations;
 pub mod msg;
 pub mod state;
+pub mod wasmswap;
 
 pub use crate::error::ContractError;
+
+#[cfg(test)]
+mod tests;
+#[cfg(test)]
+mod multitest;
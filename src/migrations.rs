@@ -0,0 +1,126 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::{Addr, Order, StdResult, Storage, Uint128};
+use cw20::Denom;
+use cw_storage_plus::Item;
+use cw_utils::Duration;
+
+use crate::dex::Dex;
+use crate::state::{AmmPair, AMM_PAIRS, Config, CONFIG, BPS_DENOMINATOR, FeeProfile, LpPolicy, WithdrawCaps, ISEXCLUDED, ROWNED, TOWNED};
+use crate::wasmswap::TokenSelect;
+
+/// Config layout stored by v0.12.x
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct ConfigV0_12 {
+    pub owner: Addr,
+    pub t_total: Uint128,
+    pub r_total: Uint128,
+    pub t_fee_total: Uint128,
+    pub tax_fee: u8,
+    pub previous_tax_fee: u8,
+    pub liquidity_fee: u8,
+    pub previous_liquidity_fee: u8,
+    pub pool_address: Addr,
+    pub swap_and_liquidity_enabled: bool,
+    pub max_tx_amount: Uint128,
+    pub num_tokens_sell_to_add_to_liquidity: Uint128,
+    pub denom: String
+}
+const CONFIG_V0_12: Item<ConfigV0_12> = Item::new("config");
+/// v0.12.x had no fee cap, migrated tokens get this one (in basis points)
+/// unless their fees already add up to more
+const V0_12_MAX_TOTAL_FEE: u16 = 2_500;
+/// v0.12.x had no timelock, migrated tokens get one day
+const V0_12_TIMELOCK: Duration = Duration::Time(24 * 60 * 60);
+/// v0.12.x only liquified on transfers, migrated tokens allow a Liquify call
+/// every hour
+const V0_12_LIQUIFY_INTERVAL: Duration = Duration::Time(60 * 60);
+/// v0.12.x demanded the exact quoted swap output, migrated tokens allow 1%
+const V0_12_MAX_SLIPPAGE_BPS: u16 = 100;
+
+/// v0.12.x -> v0.13.0
+/// Seeds the cached excluded totals, which v0.12.x recomputed from
+/// `ISEXCLUDED` on every rate lookup, converts the whole percent fees to basis
+/// point buy, sell and transfer profiles, registers the pool as the primary
+/// AMM pair and defaults the settings added since.
+/// The `previous_*` fees v0.12.x saved while zeroing fees for excluded
+/// accounts are dropped.
+pub fn migrate_config_v0_12(storage: &mut dyn Storage) -> StdResult<()> {
+    let old = CONFIG_V0_12.load(storage)?;
+
+    let excluded: Vec<Addr> = ISEXCLUDED
+        .range(storage, None, None, Order::Ascending)
+        .filter_map(|item| match item {
+            Ok((addr, true)) => Some(Ok(addr)),
+            Ok(_) => None,
+            Err(err) => Some(Err(err)),
+        })
+        .collect::<StdResult<_>>()?;
+
+    let mut excluded_r_total = Uint128::zero();
+    let mut excluded_t_total = Uint128::zero();
+    for addr in excluded {
+        excluded_r_total += ROWNED.load(storage, addr.clone()).unwrap_or(Uint128::zero());
+        excluded_t_total += TOWNED.load(storage, addr).unwrap_or(Uint128::zero());
+    }
+
+    // v0.12.x took the same fee on every transfer
+    let fee = FeeProfile {
+        tax_fee: percent_to_bps(old.tax_fee),
+        liquidity_fee: percent_to_bps(old.liquidity_fee),
+        burn_fee: 0,
+    };
+
+    let config = Config {
+        owner: Some(old.owner),
+        t_total: old.t_total,
+        r_total: old.r_total,
+        t_fee_total: old.t_fee_total,
+        t_burn_total: Uint128::zero(),
+        excluded_r_total,
+        excluded_t_total,
+        buy_fee: fee.clone(),
+        sell_fee: fee.clone(),
+        transfer_fee: fee,
+        marketing_fee: 0,
+        marketing_address: None,
+        marketing_tokens: Uint128::zero(),
+        max_total_fee: (percent_to_bps(old.tax_fee) + percent_to_bps(old.liquidity_fee))
+            .clamp(V0_12_MAX_TOTAL_FEE, BPS_DENOMINATOR),
+        pool_address: old.pool_address.clone(),
+        // v0.12.x only worked with the native denom as token1
+        denom_side: Some(TokenSelect::Token1),
+        swap_and_liquidity_enabled: old.swap_and_liquidity_enabled,
+        max_tx_amount: old.max_tx_amount,
+        num_tokens_sell_to_add_to_liquidity: old.num_tokens_sell_to_add_to_liquidity,
+        // v0.12.x capped swap and liquify at the max tx amount, the cap
+        // cannot sit below the threshold though
+        max_liquify_amount: old.max_tx_amount.max(old.num_tokens_sell_to_add_to_liquidity),
+        denom: Denom::Native(old.denom),
+        timelock: V0_12_TIMELOCK,
+        liquify_interval: V0_12_LIQUIFY_INTERVAL,
+        max_slippage_bps: V0_12_MAX_SLIPPAGE_BPS,
+        swap_expiration: None,
+        // v0.12.x kept the LP tokens for the owner to withdraw
+        lp_policy: LpPolicy::Hold,
+        // v0.12.x let the owner sweep both balances at once
+        withdraw_caps: WithdrawCaps {
+            period: V0_12_TIMELOCK,
+            lp_amount: Uint128::MAX,
+            quote_amount: Uint128::MAX,
+        }
+    };
+    CONFIG.save(storage, &config)?;
+
+    let pair = AmmPair {
+        dex: Dex::Wasmswap,
+        buy_fee: None,
+        sell_fee: None,
+    };
+    AMM_PAIRS.save(storage, old.pool_address, &pair)
+}
+
+fn percent_to_bps(percent: u8) -> u16 {
+    percent as u16 * 100
+}
@@ -2,8 +2,12 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use cosmwasm_std::{Binary, Coin, Decimal, Uint128, Addr};
-use cw20::Expiration;
+use cw20::{Denom, Expiration};
 pub use cw_controllers::ClaimsResponse;
+
+use crate::state::{AmmPair, FeeProfile, LpPolicy, PendingChange, Role, Withdrawal, WithdrawCaps};
+use crate::dex::Dex;
+use crate::wasmswap::TokenSelect;
 use cw_utils::Duration;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -15,19 +19,71 @@ pub struct InstantiateMsg {
     /// decimal places of the derivative token (for UI)
     pub decimals: u8,
 
-    /// tax_fee: 5
-    pub tax_fee: u8,
-    /// liquidity_fee: 5
-    pub liquidity_fee: u8,
+    /// Fees in basis points: tax_fee 500 = 5%
+    pub buy_fee: FeeProfile,
+    pub sell_fee: FeeProfile,
+    pub transfer_fee: FeeProfile,
+    /// Taken on every taxed transfer on top of the profiles above, in basis points
+    pub marketing_fee: u16,
+    /// Treasury receiving the marketing fee, swapped to `denom`
+    pub marketing_address: Option<String>,
+    /// Cap on the total of each fee profile plus the marketing fee in basis
+    /// points, at most 10000.
+    /// Cannot be changed later
+    pub max_total_fee: u16,
     pub pool_address: Addr,
-    pub denom: String
+    pub dex: Dex,
+    /// Native denom or cw20 paired with this token
+    pub denom: Denom,
+    /// Delay before fee, max tx and pool changes can be executed
+    pub timelock: Duration,
+    /// Minimum time between two Liquify calls
+    pub liquify_interval: Duration,
+    /// Slippage allowed on the liquify swap and liquidity add, in basis points
+    pub max_slippage_bps: u16,
+    pub swap_expiration: Option<Duration>,
+    /// What happens to the LP tokens minted by swap and liquify
+    pub lp_policy: LpPolicy,
+    /// Limits WithdrawLp and WithdrawNative over a rolling period
+    pub withdraw_caps: WithdrawCaps
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateMsg {}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
-    UpdateConfig { new_owner:String },
-    UpdatePool { address:Addr },
+    /// Proposes a new owner, who has to accept it with AcceptOwnership.
+    /// The current owner keeps every right until then.
+    ProposeNewOwner { owner: String, expiry: Option<Expiration> },
+    /// Accepts a pending ownership proposal. Must be sent by the proposed owner
+    AcceptOwnership {},
+    /// Drops a pending ownership proposal
+    CancelOwnershipProposal {},
+    /// Removes the owner for good. Every admin message is rejected afterwards
+    RenounceOwnership {},
+    /// Owner only. Lets `address` run the admin messages covered by `role`
+    GrantRole { address: String, role: Role },
+    RevokeRole { address: String, role: Role },
+    /// Queues a pool change, see ExecutePendingChange. Keeps the current dex
+    /// if unset. Also run for the initial pool once it exists, which enables
+    /// swap and liquify
+    UpdatePool { address:Addr, dex: Option<Dex> },
+    /// Queues registering another pool of this token. Transfers from and to
+    /// it are taxed as buys and sells, with the given fees instead of the
+    /// config ones when set. Also updates an already registered pool
+    AddAmmPair {
+        address: String,
+        dex: Dex,
+        buy_fee: Option<FeeProfile>,
+        sell_fee: Option<FeeProfile>
+    },
+    /// Queues unregistering a pool. The primary pool cannot be removed
+    RemoveAmmPair { address: String },
+    /// Applies a queued change once its timelock has passed
+    ExecutePendingChange { id: u64 },
+    CancelPendingChange { id: u64 },
     /// Implements CW20. Transfer is a base message to move tokens to another account without triggering actions
     Transfer { recipient: String, amount: Uint128 },
     Send {
@@ -84,11 +140,29 @@ pub enum ExecuteMsg {
     IncludeInFee {
         address: Addr
     },
-    SetTaxFeePercent {
-        percent: u8
+    /// The fee setters and SetMaxTxPercent are queued behind the timelock,
+    /// see ExecutePendingChange. Fees are in basis points: 250 = 2.5%
+    SetBuyFee {
+        tax_fee: u16,
+        liquidity_fee: u16,
+        burn_fee: u16
     },
-    SetLiquidityFeePercent {
-        percent: u8
+    SetSellFee {
+        tax_fee: u16,
+        liquidity_fee: u16,
+        burn_fee: u16
+    },
+    SetTransferFee {
+        tax_fee: u16,
+        liquidity_fee: u16,
+        burn_fee: u16
+    },
+    SetMarketingFee {
+        bps: u16
+    },
+    /// Not timelocked. Marketing fees build up in the contract while unset
+    SetMarketingAddress {
+        address: Option<String>
     },
     SetMaxTxPercent {
         percent: u8
@@ -96,13 +170,67 @@ pub enum ExecuteMsg {
     SetSwapAndLiquifyEnabled {
         enabled: bool
     },
-    FetchAdmin {}
+    /// Not timelocked. Both amounts are capped at the total supply
+    SetNumTokensSellToAddToLiquidity {
+        amount: Uint128
+    },
+    SetMaxLiquifyAmount {
+        amount: Uint128
+    },
+    SetLiquifyInterval {
+        interval: Duration
+    },
+    /// Swaps and adds liquidity with up to `amount` of the contract's tokens,
+    /// all of them if unset. Keeper role, or anyone once ownership is
+    /// renounced, at most once per liquify_interval
+    Liquify {
+        amount: Option<Uint128>
+    },
+    /// Not timelocked
+    SetLiquifySlippage {
+        max_slippage_bps: u16,
+        swap_expiration: Option<Duration>
+    },
+    /// Queued behind the timelock. Rejected while a lock is active, unless
+    /// it is a lock that ends no earlier. A burn or locker policy also takes
+    /// the LP tokens already held when it is executed
+    SetLpPolicy {
+        policy: LpPolicy
+    },
+    /// Queued behind the timelock
+    SetWithdrawCaps {
+        caps: WithdrawCaps
+    },
+    /// Sends `amount` of the primary pool LP tokens held by the contract to
+    /// `recipient`, the owner if unset. Only allowed under the hold policy or
+    /// once a lock has expired. Only the owner can pick another recipient.
+    /// Limited to `withdraw_caps.lp_amount` per period
+    WithdrawLp {
+        amount: Uint128,
+        recipient: Option<String>
+    },
+    /// Same as WithdrawLp for the quote held by the contract, native or cw20.
+    /// Limited to `withdraw_caps.quote_amount` per period
+    WithdrawNative {
+        amount: Uint128,
+        recipient: Option<String>
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     Config {},
+    /// Returns the pending ownership proposal, if any
+    OwnershipProposal {},
+    /// Returns the roles granted to the given address
+    Roles { address: String },
+    /// Returns the queued config changes
+    PendingChanges {},
+    /// Returns the registered pools, the primary one included
+    AmmPairs {},
+    /// Returns the past withdrawals, oldest first
+    Withdrawals { start_after: Option<u64>, limit: Option<u32> },
     /// Implements CW20. Returns the current balance of the given address, 0 if unset.
     Balance { address: String },
     /// Implements CW20. Returns metadata on the contract - name, decimals, supply, etc.
@@ -113,6 +241,8 @@ pub enum QueryMsg {
 
     IsExcludedFromReward {address: Addr},
     TotalFees {},
+    /// Total amount of tokens burned by the burn fee
+    TotalBurned {},
     ReflectionFromToken {t_amount: Uint128, deduct_transfer_fee: bool},
     TokenFromReflection {r_amount: Uint128},
     IsExcludedFromFee {address: Addr}
@@ -130,6 +260,38 @@ pub struct Uint128Response {
 }
 
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct OwnershipProposalResponse {
+    pub owner: Option<Addr>,
+    pub expiry: Option<Expiration>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RolesResponse {
+    pub roles: Vec<Role>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PendingChangesResponse {
+    pub changes: Vec<PendingChange>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct AmmPairInfo {
+    pub address: Addr,
+    pub pair: AmmPair,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct AmmPairsResponse {
+    pub pairs: Vec<AmmPairInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct WithdrawalsResponse {
+    pub withdrawals: Vec<Withdrawal>,
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 pub struct BoolResponse {
     pub ret: bool
@@ -138,17 +300,35 @@ pub struct BoolResponse {
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
 pub struct ConfigResponse {
-    pub owner: Addr,
+    pub owner: Option<Addr>,
+    /// Set once the ownership is renounced
+    pub frozen: bool,
     pub t_total: Uint128,
     pub r_total: Uint128,
     pub t_fee_total: Uint128,
-    pub tax_fee: u8,
-    pub previous_tax_fee: u8,
-    pub liquidity_fee: u8,
-    pub previous_liquidity_fee: u8,
+    pub t_burn_total: Uint128,
+    /// Fees are in basis points
+    pub buy_fee: FeeProfile,
+    pub sell_fee: FeeProfile,
+    pub transfer_fee: FeeProfile,
+    pub marketing_fee: u16,
+    pub marketing_address: Option<Addr>,
+    pub marketing_tokens: Uint128,
+    pub max_total_fee: u16,
+    /// Primary pool and its dex
     pub pool_address: Addr,
+    pub dex: Dex,
+    pub denom: Denom,
+    pub denom_side: Option<TokenSelect>,
     pub swap_and_liquidity_enabled: bool,
     pub max_tx_amount: Uint128,
-    pub num_tokens_sell_to_add_to_liquidity: Uint128
+    pub num_tokens_sell_to_add_to_liquidity: Uint128,
+    pub max_liquify_amount: Uint128,
+    pub timelock: Duration,
+    pub liquify_interval: Duration,
+    pub max_slippage_bps: u16,
+    pub swap_expiration: Option<Duration>,
+    pub lp_policy: LpPolicy,
+    pub withdraw_caps: WithdrawCaps
 
 }
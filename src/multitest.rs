@@ -0,0 +1,504 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::testing::mock_env;
+use cosmwasm_std::{
+    coin, coins, from_json, to_json_binary, Addr, Binary, CosmosMsg, Decimal, Deps, DepsMut, Empty, Env,
+    MessageInfo, Response, StdError, StdResult, Uint128, WasmMsg,
+};
+use cw20::{BalanceResponse, Cw20Coin, Cw20ExecuteMsg, Cw20QueryMsg, Cw20ReceiveMsg, Denom, Expiration};
+use cw_multi_test::{App, Contract, ContractWrapper, Executor};
+use cw_storage_plus::{Item, Map};
+use cw_utils::Duration;
+
+use crate::astroport::{self, Asset, AssetInfo};
+use crate::contract::{execute, instantiate, query, reply};
+use crate::dex::{denom_transfer_msg, Dex, PoolInfo};
+use crate::msg::{ExecuteMsg, InstantiateMsg};
+use crate::state::{FeeProfile, LpPolicy, WithdrawCaps};
+use crate::wasmswap::{self, TokenSelect};
+use crate::ContractError;
+
+const OWNER: &str = "owner";
+const DEAD: &str = "dead";
+const JUNO: &str = "ujuno";
+const ATOM: &str = "uatom";
+
+/// A constant product pool, which is also its own LP token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+struct MockPool {
+    assets: [Denom; 2],
+    reserves: [Uint128; 2],
+    lp_supply: Uint128,
+}
+
+const MOCK_POOL: Item<MockPool> = Item::new("mock_pool");
+const LP_BALANCES: Map<&Addr, Uint128> = Map::new("lp_balances");
+
+/// The assets have to be sent to the pair along with it, the LP supply is
+/// minted to the sender
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+struct MockPairInstantiateMsg {
+    assets: [Denom; 2],
+    reserves: [Uint128; 2],
+    lp_supply: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum MockWasmswapExecuteMsg {
+    AddLiquidity {
+        token1_amount: Uint128,
+        min_liquidity: Uint128,
+        max_token2: Uint128,
+        expiration: Option<Expiration>,
+    },
+    Swap {
+        input_token: TokenSelect,
+        input_amount: Uint128,
+        min_output: Uint128,
+        expiration: Option<Expiration>,
+    },
+    Transfer { recipient: String, amount: Uint128 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum MockAstroportExecuteMsg {
+    ProvideLiquidity {
+        assets: [Asset; 2],
+        slippage_tolerance: Option<Decimal>,
+        receiver: Option<String>,
+    },
+    Receive(Cw20ReceiveMsg),
+    Transfer { recipient: String, amount: Uint128 },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum MockPairQueryMsg {
+    Info {},
+    Pair {},
+    Pool {},
+    Balance { address: String },
+}
+
+fn mock_pair_instantiate(deps: DepsMut, _env: Env, info: MessageInfo, msg: MockPairInstantiateMsg) -> StdResult<Response> {
+    MOCK_POOL.save(deps.storage, &MockPool {
+        assets: msg.assets,
+        reserves: msg.reserves,
+        lp_supply: msg.lp_supply,
+    })?;
+    LP_BALANCES.save(deps.storage, &info.sender, &msg.lp_supply)?;
+    Ok(Response::new())
+}
+
+fn mock_wasmswap_execute(deps: DepsMut, env: Env, info: MessageInfo, msg: MockWasmswapExecuteMsg) -> StdResult<Response> {
+    let mut pool = MOCK_POOL.load(deps.storage)?;
+    match msg {
+        MockWasmswapExecuteMsg::Swap { input_token, input_amount, min_output, .. } => {
+            let (input, output) = match input_token {
+                TokenSelect::Token1 => (0, 1),
+                TokenSelect::Token2 => (1, 0),
+            };
+            let output_amount = mock_input_price(input_amount, pool.reserves[input], pool.reserves[output]);
+            if output_amount < min_output {
+                return Err(StdError::generic_err("Swap min error"));
+            }
+            pool.reserves[input] += input_amount;
+            pool.reserves[output] -= output_amount;
+            MOCK_POOL.save(deps.storage, &pool)?;
+
+            Ok(Response::new()
+                .add_messages(mock_pull(&pool.assets[input], &info, &env, input_amount)?)
+                .add_message(denom_transfer_msg(&pool.assets[output], &info.sender, output_amount)?))
+        }
+        MockWasmswapExecuteMsg::AddLiquidity { token1_amount, min_liquidity, max_token2, .. } => {
+            let liquidity = token1_amount.multiply_ratio(pool.lp_supply, pool.reserves[0]);
+            let token2_amount = token1_amount.multiply_ratio(pool.reserves[1], pool.reserves[0]) + Uint128::new(1);
+            if liquidity < min_liquidity {
+                return Err(StdError::generic_err("Min liquidity error"));
+            }
+            if token2_amount > max_token2 {
+                return Err(StdError::generic_err("Max token2 error"));
+            }
+            pool.reserves[0] += token1_amount;
+            pool.reserves[1] += token2_amount;
+            pool.lp_supply += liquidity;
+            MOCK_POOL.save(deps.storage, &pool)?;
+            mock_mint(deps, &info.sender, liquidity)?;
+
+            // a native token2 comes as max_token2 and the rest is refunded
+            let mut res = Response::new().add_messages(mock_pull(&pool.assets[0], &info, &env, token1_amount)?);
+            match &pool.assets[1] {
+                Denom::Native(_) => {
+                    res = res.add_messages(mock_pull(&pool.assets[1], &info, &env, max_token2)?);
+                    if max_token2 > token2_amount {
+                        res = res.add_message(denom_transfer_msg(&pool.assets[1], &info.sender, max_token2 - token2_amount)?);
+                    }
+                }
+                Denom::Cw20(_) => res = res.add_messages(mock_pull(&pool.assets[1], &info, &env, token2_amount)?),
+            }
+            Ok(res)
+        }
+        MockWasmswapExecuteMsg::Transfer { recipient, amount } => mock_lp_transfer(deps, info, recipient, amount),
+    }
+}
+
+fn mock_astroport_execute(deps: DepsMut, env: Env, info: MessageInfo, msg: MockAstroportExecuteMsg) -> StdResult<Response> {
+    let mut pool = MOCK_POOL.load(deps.storage)?;
+    match msg {
+        MockAstroportExecuteMsg::Receive(Cw20ReceiveMsg { sender, amount, msg }) => {
+            let astroport::Cw20HookMsg::Swap { belief_price, max_spread, to } = from_json(&msg)?;
+            let offered = Denom::Cw20(info.sender.clone());
+            let input = pool.assets.iter().position(|asset| *asset == offered)
+                .ok_or_else(|| StdError::generic_err("Asset mismatch"))?;
+            let output = 1 - input;
+
+            let return_amount = mock_input_price(amount, pool.reserves[input], pool.reserves[output]);
+            if let (Some(belief_price), Some(max_spread)) = (belief_price, max_spread) {
+                let expected = amount * (Decimal::one() / belief_price);
+                if return_amount < expected * (Decimal::one() - max_spread) {
+                    return Err(StdError::generic_err("Operation exceeds max spread limit"));
+                }
+            }
+            pool.reserves[input] += amount;
+            pool.reserves[output] -= return_amount;
+            MOCK_POOL.save(deps.storage, &pool)?;
+
+            let receiver = Addr::unchecked(to.unwrap_or(sender));
+            Ok(Response::new().add_message(denom_transfer_msg(&pool.assets[output], &receiver, return_amount)?))
+        }
+        MockAstroportExecuteMsg::ProvideLiquidity { assets, slippage_tolerance, receiver } => {
+            let mut deposits = [Uint128::zero(); 2];
+            for asset in assets.iter() {
+                let denom = Denom::from(asset.info.clone());
+                let index = pool.assets.iter().position(|pool_asset| *pool_asset == denom)
+                    .ok_or_else(|| StdError::generic_err("Asset mismatch"))?;
+                deposits[index] = asset.amount;
+            }
+            if let Some(tolerance) = slippage_tolerance {
+                let pool_ratio = Decimal::from_ratio(pool.reserves[0], pool.reserves[1]);
+                let deposit_ratio = Decimal::from_ratio(deposits[0], deposits[1]);
+                let one_minus_tolerance = Decimal::one() - tolerance;
+                if deposit_ratio * one_minus_tolerance > pool_ratio || pool_ratio * one_minus_tolerance > deposit_ratio {
+                    return Err(StdError::generic_err("Operation exceeds max splippage tolerance"));
+                }
+            }
+            let share = std::cmp::min(
+                deposits[0].multiply_ratio(pool.lp_supply, pool.reserves[0]),
+                deposits[1].multiply_ratio(pool.lp_supply, pool.reserves[1]),
+            );
+            let mut res = Response::new();
+            for (index, deposit) in deposits.iter().enumerate() {
+                res = res.add_messages(mock_pull(&pool.assets[index], &info, &env, *deposit)?);
+                pool.reserves[index] += *deposit;
+            }
+            pool.lp_supply += share;
+            MOCK_POOL.save(deps.storage, &pool)?;
+
+            let receiver = match receiver {
+                Some(receiver) => deps.api.addr_validate(&receiver)?,
+                None => info.sender,
+            };
+            mock_mint(deps, &receiver, share)?;
+            Ok(res)
+        }
+        MockAstroportExecuteMsg::Transfer { recipient, amount } => mock_lp_transfer(deps, info, recipient, amount),
+    }
+}
+
+fn mock_pair_query(deps: Deps, env: Env, msg: MockPairQueryMsg) -> StdResult<Binary> {
+    let pool = MOCK_POOL.load(deps.storage)?;
+    match msg {
+        MockPairQueryMsg::Info {} => to_json_binary(&wasmswap::InfoResponse {
+            token1_reserve: pool.reserves[0],
+            token1_denom: pool.assets[0].clone(),
+            token2_reserve: pool.reserves[1],
+            token2_denom: pool.assets[1].clone(),
+            lp_token_supply: pool.lp_supply,
+            lp_token_address: env.contract.address.into(),
+        }),
+        MockPairQueryMsg::Pair {} => to_json_binary(&astroport::PairInfo {
+            liquidity_token: env.contract.address.into(),
+        }),
+        MockPairQueryMsg::Pool {} => {
+            let asset = |index: usize| Asset {
+                info: AssetInfo::from(pool.assets[index].clone()),
+                amount: pool.reserves[index],
+            };
+            to_json_binary(&astroport::PoolResponse {
+                assets: [asset(0), asset(1)],
+                total_share: pool.lp_supply,
+            })
+        }
+        MockPairQueryMsg::Balance { address } => {
+            let address = deps.api.addr_validate(&address)?;
+            let balance = LP_BALANCES.may_load(deps.storage, &address)?.unwrap_or_default();
+            to_json_binary(&BalanceResponse { balance })
+        }
+    }
+}
+
+fn mock_input_price(input_amount: Uint128, input_reserve: Uint128, output_reserve: Uint128) -> Uint128 {
+    let input_amount_with_fee = input_amount * Uint128::new(997);
+    (input_amount_with_fee * output_reserve) / (input_reserve * Uint128::new(1000) + input_amount_with_fee)
+}
+
+/// Native assets have to be sent along, cw20 ones are pulled from the sender
+fn mock_pull(asset: &Denom, info: &MessageInfo, env: &Env, amount: Uint128) -> StdResult<Vec<CosmosMsg>> {
+    match asset {
+        Denom::Native(denom) => {
+            let sent: Uint128 = info.funds.iter().filter(|coin| coin.denom == *denom).map(|coin| coin.amount).sum();
+            if sent != amount {
+                return Err(StdError::generic_err(format!("Expected {}{} in funds, got {}", amount, denom, sent)));
+            }
+            Ok(vec![])
+        }
+        Denom::Cw20(token) => Ok(vec![CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: token.to_string(),
+            msg: to_json_binary(&Cw20ExecuteMsg::TransferFrom {
+                owner: info.sender.to_string(),
+                recipient: env.contract.address.to_string(),
+                amount,
+            })?,
+            funds: vec![],
+        })]),
+    }
+}
+
+fn mock_mint(deps: DepsMut, recipient: &Addr, amount: Uint128) -> StdResult<()> {
+    LP_BALANCES.update(deps.storage, recipient, |balance| -> StdResult<_> {
+        Ok(balance.unwrap_or_default() + amount)
+    })?;
+    Ok(())
+}
+
+fn mock_lp_transfer(deps: DepsMut, info: MessageInfo, recipient: String, amount: Uint128) -> StdResult<Response> {
+    LP_BALANCES.update(deps.storage, &info.sender, |balance| -> StdResult<_> {
+        Ok(balance.unwrap_or_default().checked_sub(amount)?)
+    })?;
+    let recipient = deps.api.addr_validate(&recipient)?;
+    mock_mint(deps, &recipient, amount)?;
+    Ok(Response::new())
+}
+
+fn token_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(execute, instantiate, query).with_reply(reply))
+}
+
+fn cw20_contract() -> Box<dyn Contract<Empty>> {
+    Box::new(ContractWrapper::new(
+        cw20_base::contract::execute,
+        cw20_base::contract::instantiate,
+        cw20_base::contract::query,
+    ))
+}
+
+fn pair_contract(dex: &Dex) -> Box<dyn Contract<Empty>> {
+    match dex {
+        Dex::Wasmswap => Box::new(ContractWrapper::new(mock_wasmswap_execute, mock_pair_instantiate, mock_pair_query)),
+        Dex::Astroport => Box::new(ContractWrapper::new(mock_astroport_execute, mock_pair_instantiate, mock_pair_query)),
+    }
+}
+
+struct Suite {
+    app: App,
+    token: Addr,
+    quote: Denom,
+    dex: Dex,
+}
+
+impl Suite {
+    /// A token quoted in juno, or in a cw20 when `cw20_quote` is set
+    fn new(dex: Dex, cw20_quote: bool) -> Suite {
+        let owner = Addr::unchecked(OWNER);
+        let mut app = App::new(|router, _, storage| {
+            let balance = vec![coin(1_000_000_000, JUNO), coin(1_000_000_000, ATOM)];
+            router.bank.init_balance(storage, &owner, balance).unwrap();
+        });
+
+        let quote = if cw20_quote {
+            let code_id = app.store_code(cw20_contract());
+            let quote = app.instantiate_contract(code_id, owner.clone(), &cw20_base::msg::InstantiateMsg {
+                name: "Quote".to_string(),
+                symbol: "QUOTE".to_string(),
+                decimals: 6,
+                initial_balances: vec![Cw20Coin { address: OWNER.to_string(), amount: Uint128::new(1_000_000_000) }],
+                mint: None,
+                marketing: None,
+            }, &[], "quote", None).unwrap();
+            Denom::Cw20(quote)
+        } else {
+            Denom::Native(JUNO.to_string())
+        };
+
+        let fee = FeeProfile { tax_fee: 500, liquidity_fee: 500, burn_fee: 0 };
+        let code_id = app.store_code(token_contract());
+        let token = app.instantiate_contract(code_id, owner.clone(), &InstantiateMsg {
+            name: "Safemoon".to_string(),
+            symbol: "SAFE".to_string(),
+            decimals: 0,
+            buy_fee: fee.clone(),
+            sell_fee: fee.clone(),
+            transfer_fee: fee,
+            marketing_fee: 0,
+            marketing_address: None,
+            max_total_fee: 2_500,
+            // the pool needs the token address, see `add_pool`
+            pool_address: Addr::unchecked("unlisted"),
+            dex: dex.clone(),
+            denom: quote.clone(),
+            timelock: Duration::Time(60),
+            liquify_interval: Duration::Time(3_600),
+            max_slippage_bps: 100,
+            swap_expiration: None,
+            lp_policy: LpPolicy::Burn { dead_address: Addr::unchecked(DEAD) },
+            withdraw_caps: WithdrawCaps {
+                period: Duration::Time(24 * 60 * 60),
+                lp_amount: Uint128::zero(),
+                quote_amount: Uint128::zero(),
+            },
+        }, &[], "safemoon", None).unwrap();
+
+        Suite { app, token, quote, dex }
+    }
+
+    fn execute_token(&mut self, sender: &str, msg: &ExecuteMsg) -> Result<(), ContractError> {
+        self.app
+            .execute_contract(Addr::unchecked(sender), self.token.clone(), msg, &[])
+            .map(|_| ())
+            .map_err(|err| err.downcast().unwrap())
+    }
+
+    fn transfer(&mut self, sender: &str, recipient: &str, amount: u128) {
+        let msg = ExecuteMsg::Transfer { recipient: recipient.to_string(), amount: Uint128::new(amount) };
+        self.execute_token(sender, &msg).unwrap();
+    }
+
+    /// Creates a pair of `assets` holding `reserves`, seeded by the owner
+    fn create_pair(&mut self, assets: [Denom; 2], reserves: [u128; 2]) -> Addr {
+        let owner = Addr::unchecked(OWNER);
+        let code_id = self.app.store_code(pair_contract(&self.dex));
+        let pair = self.app.instantiate_contract(code_id, owner.clone(), &MockPairInstantiateMsg {
+            assets: assets.clone(),
+            reserves: [Uint128::new(reserves[0]), Uint128::new(reserves[1])],
+            lp_supply: Uint128::new(1_000_000),
+        }, &[], "pair", None).unwrap();
+
+        for (asset, reserve) in assets.iter().zip(reserves.iter()) {
+            match asset {
+                Denom::Native(denom) => {
+                    self.app.send_tokens(owner.clone(), pair.clone(), &coins(*reserve, denom.as_str())).unwrap();
+                }
+                Denom::Cw20(token) => {
+                    let msg = Cw20ExecuteMsg::Transfer { recipient: pair.to_string(), amount: Uint128::new(*reserve) };
+                    self.app.execute_contract(owner.clone(), token.clone(), &msg, &[]).unwrap();
+                }
+            }
+        }
+        pair
+    }
+
+    /// Makes `pair` the primary pool once the timelock has passed
+    fn add_pool(&mut self, pair: &Addr) -> Result<(), ContractError> {
+        let msg = ExecuteMsg::UpdatePool { address: pair.clone(), dex: Some(self.dex.clone()) };
+        self.execute_token(OWNER, &msg)?;
+        self.app.update_block(|block| block.time = block.time.plus_seconds(60));
+        self.execute_token(OWNER, &ExecuteMsg::ExecutePendingChange { id: 1 })
+    }
+
+    /// A pool of 1M quote and 10M tokens on `denom_side`
+    fn list(&mut self, denom_side: TokenSelect) -> Addr {
+        let token = Denom::Cw20(self.token.clone());
+        let pair = match denom_side {
+            TokenSelect::Token1 => self.create_pair([self.quote.clone(), token], [1_000_000, 10_000_000]),
+            TokenSelect::Token2 => self.create_pair([token, self.quote.clone()], [10_000_000, 1_000_000]),
+        };
+        self.add_pool(&pair).unwrap();
+        pair
+    }
+
+    fn query_pool(&self, pair: &Addr) -> PoolInfo {
+        let mut env = mock_env();
+        env.contract.address = self.token.clone();
+        self.dex.query_pool(self.app.wrap(), &env, pair, &self.quote).unwrap()
+    }
+
+    fn balance(&self, denom: &Denom, address: &Addr) -> Uint128 {
+        match denom {
+            Denom::Native(denom) => self.app.wrap().query_balance(address, denom).unwrap().amount,
+            Denom::Cw20(token) => {
+                let res: BalanceResponse = self.app.wrap()
+                    .query_wasm_smart(token, &Cw20QueryMsg::Balance { address: address.to_string() })
+                    .unwrap();
+                res.balance
+            }
+        }
+    }
+}
+
+fn liquify_through_pool(dex: Dex, denom_side: TokenSelect, cw20_quote: bool) {
+    let mut suite = Suite::new(dex, cw20_quote);
+    let pair = suite.list(denom_side.clone());
+    let token = Denom::Cw20(suite.token.clone());
+    let lp_token = Denom::Cw20(pair.clone());
+    let before = suite.query_pool(&pair);
+    assert_eq!(before.denom_side, denom_side);
+
+    // fill the contract up to the 5k threshold, the next transfer sells it
+    let contract = suite.token.to_string();
+    suite.transfer(OWNER, &contract, 5_000);
+    suite.transfer(OWNER, "alice", 100_000);
+    suite.transfer("alice", "bob", 1_000);
+
+    // half was sold, most of the other half paired with what it bought
+    let after = suite.query_pool(&pair);
+    let added = after.token_reserve - before.token_reserve;
+    assert!(added >= Uint128::new(4_950) && added <= Uint128::new(5_000), "{} tokens added", added);
+    assert!(suite.balance(&suite.quote, &suite.token) <= Uint128::new(1));
+
+    // the reply forwarded every minted LP token to the dead address
+    let minted = after.lp_token_supply - before.lp_token_supply;
+    assert!(!minted.is_zero());
+    assert_eq!(suite.balance(&lp_token, &Addr::unchecked(DEAD)), minted);
+    assert_eq!(suite.balance(&lp_token, &suite.token), Uint128::zero());
+    assert_eq!(suite.balance(&token, &pair), after.token_reserve);
+}
+
+#[test]
+fn wasmswap_liquify() {
+    for denom_side in [TokenSelect::Token1, TokenSelect::Token2].iter() {
+        for cw20_quote in [false, true].iter() {
+            liquify_through_pool(Dex::Wasmswap, denom_side.clone(), *cw20_quote);
+        }
+    }
+}
+
+#[test]
+fn astroport_liquify() {
+    for denom_side in [TokenSelect::Token1, TokenSelect::Token2].iter() {
+        for cw20_quote in [false, true].iter() {
+            liquify_through_pool(Dex::Astroport, denom_side.clone(), *cw20_quote);
+        }
+    }
+}
+
+#[test]
+fn pools_of_other_assets_are_rejected() {
+    for dex in [Dex::Wasmswap, Dex::Astroport].iter() {
+        let mut suite = Suite::new(dex.clone(), false);
+        let token = Denom::Cw20(suite.token.clone());
+        let atom = Denom::Native(ATOM.to_string());
+
+        let pairs = vec![
+            suite.create_pair([Denom::Native(JUNO.to_string()), atom.clone()], [1_000_000, 1_000_000]),
+            suite.create_pair([atom, token.clone()], [1_000_000, 10_000_000]),
+            suite.create_pair([token.clone(), token], [1_000_000, 1_000_000]),
+        ];
+        for pair in pairs {
+            let err = suite.add_pool(&pair).unwrap_err();
+            assert_eq!(err, ContractError::InvalidPool { pool: pair.to_string() });
+        }
+    }
+}
@@ -1,30 +1,328 @@
+use std::fmt;
+
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Addr, Decimal, Uint128};
+use cosmwasm_std::{Addr, BlockInfo, Decimal, Timestamp, Uint128};
 use cw_controllers::Claims;
-use cw_utils::Duration;
+use cw20::Denom;
+use cw_utils::{Duration, Expiration};
 use cw_storage_plus::{Item, Map};
 
+use crate::dex::{Dex, SwapLimits};
+use crate::wasmswap::TokenSelect;
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
     /// Owner If None set, contract is frozen.
-    pub owner: Addr,
+    pub owner: Option<Addr>,
     pub t_total: Uint128,
     pub r_total: Uint128,
     pub t_fee_total: Uint128,
-    pub tax_fee: u8,
-    pub previous_tax_fee: u8,
-    pub liquidity_fee: u8,
-    pub previous_liquidity_fee: u8,
+    /// Tokens removed from t_total by the burn fee
+    pub t_burn_total: Uint128,
+    /// Sum of `ROWNED` over the accounts in `ISEXCLUDED`
+    pub excluded_r_total: Uint128,
+    /// Sum of `TOWNED` over the accounts in `ISEXCLUDED`
+    pub excluded_t_total: Uint128,
+    /// Fees taken on buys from, sells into the pool and other transfers
+    pub buy_fee: FeeProfile,
+    pub sell_fee: FeeProfile,
+    pub transfer_fee: FeeProfile,
+    /// Taken on every taxed transfer on top of the fee profile, and paid out
+    /// to marketing_address in `denom` during swap and liquify
+    pub marketing_fee: u16,
+    pub marketing_address: Option<Addr>,
+    /// Marketing fee tokens held by the contract, waiting to be swapped
+    pub marketing_tokens: Uint128,
+    /// Cap on the total of each fee profile plus the marketing fee, fixed at
+    /// instantiate
+    pub max_total_fee: u16,
+    /// Primary pool, used for swap and liquify. Always registered in
+    /// `AMM_PAIRS`
     pub pool_address: Addr,
+    /// Side of the primary pool holding `denom`, checked when the pool is set.
+    /// None until then, which disables swap and liquify
+    pub denom_side: Option<TokenSelect>,
     pub swap_and_liquidity_enabled: bool,
     pub max_tx_amount: Uint128,
+    /// Contract balance that triggers swap and liquify
     pub num_tokens_sell_to_add_to_liquidity: Uint128,
-    pub denom: String
+    /// Most tokens a keeper's Liquify sells, never below the threshold a
+    /// transfer sells
+    pub max_liquify_amount: Uint128,
+    /// Asset paired with this token, swapped into for the marketing payout
+    /// and liquidity
+    pub denom: Denom,
+    /// Delay between queueing a fee, max tx or pool change and executing it
+    pub timelock: Duration,
+    /// Minimum time between two Liquify calls
+    pub liquify_interval: Duration,
+    /// How far below the quoted amounts the liquify swap and liquidity add
+    /// may settle, in basis points
+    pub max_slippage_bps: u16,
+    /// Liquify swaps and liquidity adds expire this long after the block
+    /// that sent them
+    pub swap_expiration: Option<Duration>,
+    /// What happens to the LP tokens minted by swap and liquify
+    pub lp_policy: LpPolicy,
+    pub withdraw_caps: WithdrawCaps
 }
 pub const CONFIG: Item<Config> = Item::new("config");
 
+impl Config {
+    pub fn fee_profile(&self, kind: TransferKind) -> &FeeProfile {
+        match kind {
+            TransferKind::Buy => &self.buy_fee,
+            TransferKind::Sell => &self.sell_fee,
+            TransferKind::Transfer => &self.transfer_fee,
+        }
+    }
+
+    pub fn fee_profile_mut(&mut self, kind: TransferKind) -> &mut FeeProfile {
+        match kind {
+            TransferKind::Buy => &mut self.buy_fee,
+            TransferKind::Sell => &mut self.sell_fee,
+            TransferKind::Transfer => &mut self.transfer_fee,
+        }
+    }
+
+    /// Limits of a swap and liquify started at `block`
+    pub fn swap_limits(&self, block: &BlockInfo) -> SwapLimits {
+        SwapLimits {
+            max_slippage_bps: self.max_slippage_bps,
+            expiration: self.swap_expiration.map(|window| window.after(block)),
+        }
+    }
+}
+
+/// Fees in basis points, see `BPS_DENOMINATOR`
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct FeeProfile {
+    /// Reflected to holders
+    pub tax_fee: u16,
+    /// Kept by the contract for swap and liquify
+    pub liquidity_fee: u16,
+    /// Removed from the supply
+    pub burn_fee: u16,
+}
+
+impl FeeProfile {
+    pub fn total(&self) -> u32 {
+        self.tax_fee as u32 + self.liquidity_fee as u32 + self.burn_fee as u32
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferKind {
+    /// Sent by the pool
+    Buy,
+    /// Sent to the pool
+    Sell,
+    /// Wallet to wallet
+    Transfer,
+}
+
+impl fmt::Display for TransferKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransferKind::Buy => write!(f, "buy"),
+            TransferKind::Sell => write!(f, "sell"),
+            TransferKind::Transfer => write!(f, "transfer"),
+        }
+    }
+}
+
+/// A pool of this token, transfers from it are buys and transfers to it sells
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct AmmPair {
+    pub dex: Dex,
+    /// Replace the config buy and sell fees for this pool when set
+    pub buy_fee: Option<FeeProfile>,
+    pub sell_fee: Option<FeeProfile>,
+}
+pub const AMM_PAIRS: Map<Addr, AmmPair> = Map::new("amm_pairs");
+
+impl AmmPair {
+    pub fn fee_profile(&self, kind: TransferKind) -> Option<&FeeProfile> {
+        match kind {
+            TransferKind::Buy => self.buy_fee.as_ref(),
+            TransferKind::Sell => self.sell_fee.as_ref(),
+            TransferKind::Transfer => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LpPolicy {
+    /// Kept by the contract, WithdrawLp can withdraw them
+    Hold,
+    /// Sent to `dead_address` as they are minted
+    Burn { dead_address: Addr },
+    /// Kept by the contract, WithdrawLp can only withdraw them after `until`.
+    /// Can only be extended until then
+    Lock { until: Expiration },
+    /// Sent to a locker contract as they are minted
+    Locker { address: Addr },
+}
+
+impl LpPolicy {
+    /// Address the minted LP tokens are forwarded to, if any
+    pub fn recipient(&self) -> Option<&Addr> {
+        match self {
+            LpPolicy::Burn { dead_address } => Some(dead_address),
+            LpPolicy::Locker { address } => Some(address),
+            LpPolicy::Hold | LpPolicy::Lock { .. } => None,
+        }
+    }
+}
+
+/// Most LP tokens and quote denom that WithdrawLp and WithdrawNative can send
+/// out within any `period`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct WithdrawCaps {
+    pub period: Duration,
+    pub lp_amount: Uint128,
+    pub quote_amount: Uint128,
+}
+
+impl fmt::Display for LpPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LpPolicy::Hold => write!(f, "hold"),
+            LpPolicy::Burn { .. } => write!(f, "burn"),
+            LpPolicy::Lock { .. } => write!(f, "lock"),
+            LpPolicy::Locker { .. } => write!(f, "locker"),
+        }
+    }
+}
+
+/// 100% in basis points
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OwnershipProposal {
+    /// Address that has to accept the ownership
+    pub owner: Addr,
+    /// The proposal cannot be accepted after this
+    pub expiry: Option<Expiration>,
+}
+pub const OWNERSHIP_PROPOSAL: Item<OwnershipProposal> = Item::new("ownership_proposal");
+
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigChange {
+    Fee { kind: TransferKind, fee: FeeProfile },
+    MarketingFee { bps: u16 },
+    MaxTxPercent { percent: u8 },
+    PoolAddress { address: Addr, dex: Dex },
+    AddAmmPair { address: Addr, pair: AmmPair },
+    RemoveAmmPair { address: Addr },
+    LpPolicy { policy: LpPolicy },
+    WithdrawCaps { caps: WithdrawCaps },
+}
+
+impl ConfigChange {
+    /// Role allowed to execute or cancel the change
+    pub fn role(&self) -> Role {
+        match self {
+            ConfigChange::Fee { .. }
+            | ConfigChange::MarketingFee { .. }
+            | ConfigChange::MaxTxPercent { .. } => Role::FeeManager,
+            ConfigChange::PoolAddress { .. }
+            | ConfigChange::AddAmmPair { .. }
+            | ConfigChange::RemoveAmmPair { .. }
+            | ConfigChange::LpPolicy { .. }
+            | ConfigChange::WithdrawCaps { .. } => Role::LiquidityManager,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingChange {
+    pub id: u64,
+    pub change: ConfigChange,
+    /// The change can be executed once this is expired
+    pub executable_at: Expiration,
+}
+pub const PENDING_CHANGES: Map<u64, PendingChange> = Map::new("pending_changes");
+pub const PENDING_CHANGE_SEQ: Item<u64> = Item::new("pending_change_seq");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// SetBuyFee, SetSellFee, SetTransferFee, SetMarketingFee,
+    /// SetMarketingAddress, SetMaxTxPercent
+    FeeManager,
+    /// UpdatePool, AddAmmPair, RemoveAmmPair, SetSwapAndLiquifyEnabled,
+    /// SetNumTokensSellToAddToLiquidity, SetMaxLiquifyAmount,
+    /// SetLiquifyInterval, SetLiquifySlippage, SetLpPolicy, SetWithdrawCaps,
+    /// WithdrawLp, WithdrawNative
+    LiquidityManager,
+    /// ExcludeFromReward, IncludeInReward, ExcludeFromFee, IncludeInFee
+    ExclusionManager,
+    /// Liquify, open to anyone once ownership is renounced
+    Keeper,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Role::FeeManager => write!(f, "fee_manager"),
+            Role::LiquidityManager => write!(f, "liquidity_manager"),
+            Role::ExclusionManager => write!(f, "exclusion_manager"),
+            Role::Keeper => write!(f, "keeper"),
+        }
+    }
+}
+
+/// Roles granted to each address. The owner implicitly holds all of them
+pub const ROLES: Map<Addr, Vec<Role>> = Map::new("roles");
+
+/// Swap sent by `swap_and_liquify`, completed in its reply
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingLiquify {
+    /// Quote denom balance before the swap
+    pub initial_balance: Uint128,
+    /// Tokens sold, including the marketing share
+    pub swap_amount: Uint128,
+    /// Part of swap_amount whose proceeds go to the treasury
+    pub marketing_amount: Uint128,
+    /// Tokens kept back to pair with the swap output
+    pub token_amount: Uint128,
+}
+pub const PENDING_LIQUIFY: Item<PendingLiquify> = Item::new("pending_liquify");
+/// Liquidity add sent by `add_liquidity` whose LP tokens are forwarded by
+/// the LP policy, completed in its reply
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PendingLiquidity {
+    pub lp_token: Addr,
+    /// LP token balance before the liquidity add
+    pub initial_balance: Uint128,
+}
+pub const PENDING_LIQUIDITY: Item<PendingLiquidity> = Item::new("pending_liquidity");
+/// Liquify is rejected until this has expired
+pub const NEXT_LIQUIFY: Item<Expiration> = Item::new("next_liquify");
+
+/// LP tokens or quote denom sent out of the contract by WithdrawLp or
+/// WithdrawNative
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Withdrawal {
+    pub id: u64,
+    /// The LP token or the quote denom
+    pub asset: Denom,
+    pub amount: Uint128,
+    pub recipient: Addr,
+    /// Sender of the withdraw message
+    pub by: Addr,
+    pub height: u64,
+    pub time: Timestamp,
+}
+pub const WITHDRAWALS: Map<u64, Withdrawal> = Map::new("withdrawals");
+pub const WITHDRAWAL_SEQ: Item<u64> = Item::new("withdrawal_seq");
 
 pub const ROWNED: Map<Addr, Uint128> = Map::new("_rOwned");
 pub const TOWNED: Map<Addr, Uint128> = Map::new("_tOwned");
@@ -0,0 +1,1444 @@
+use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+use cosmwasm_std::{
+    coins, from_json, to_json_binary, Addr, BankMsg, Binary, Coin, ContractResult, CosmosMsg, Deps, Order, OwnedDeps, Reply,
+    ReplyOn, Response, StdResult, SubMsgResponse, SubMsgResult, SystemError, SystemResult, Uint128, WasmMsg,
+    WasmQuery,
+};
+use cw2::{get_contract_version, set_contract_version};
+use cw20::{BalanceResponse, Cw20ExecuteMsg, Cw20QueryMsg, Denom, Expiration};
+use cw20_base::state::TOKEN_INFO;
+use cw_storage_plus::Item;
+use cw_utils::Duration;
+
+use crate::contract::{execute, instantiate, migrate, query, reply, LIQUIFY_PROVIDE_REPLY_ID, LIQUIFY_SWAP_REPLY_ID};
+use crate::dex::Dex;
+use crate::migrations::ConfigV0_12;
+use crate::msg::{
+    AmmPairInfo, AmmPairsResponse, ConfigResponse, ExecuteMsg, InstantiateMsg, MigrateMsg, OwnershipProposalResponse,
+    PendingChangesResponse, QueryMsg, RolesResponse, Uint128Response, WithdrawalsResponse,
+};
+use crate::state::{AmmPair, ConfigChange, FeeProfile, LpPolicy, Role, TransferKind, Withdrawal, WithdrawCaps, AMM_PAIRS, CONFIG, ISEXCLUDED, ROWNED, TOWNED};
+use crate::wasmswap::{ExecuteMsg as WasmswapExecuteMsg, InfoResponse, TokenSelect};
+use crate::ContractError;
+
+type MockDeps = OwnedDeps<MockStorage, MockApi, MockQuerier>;
+
+const OWNER: &str = "owner";
+const POOL: &str = "pool";
+const DENOM: &str = "ujuno";
+
+fn fee(tax_fee: u16, liquidity_fee: u16, burn_fee: u16) -> FeeProfile {
+    FeeProfile { tax_fee, liquidity_fee, burn_fee }
+}
+
+fn instantiate_msg() -> InstantiateMsg {
+    InstantiateMsg {
+        name: "Safemoon".to_string(),
+        symbol: "SAFE".to_string(),
+        // 1G tokens in total
+        decimals: 0,
+        buy_fee: fee(500, 500, 0),
+        sell_fee: fee(500, 500, 0),
+        transfer_fee: fee(500, 500, 0),
+        marketing_fee: 0,
+        marketing_address: None,
+        max_total_fee: 2_500,
+        pool_address: Addr::unchecked(POOL),
+        dex: Dex::Wasmswap,
+        denom: Denom::Native(DENOM.to_string()),
+        timelock: Duration::Time(60),
+        liquify_interval: Duration::Time(3_600),
+        max_slippage_bps: 100,
+        swap_expiration: None,
+        lp_policy: LpPolicy::Hold,
+        withdraw_caps: WithdrawCaps {
+            period: Duration::Time(24 * 60 * 60),
+            lp_amount: Uint128::new(10_000),
+            quote_amount: Uint128::new(500),
+        },
+    }
+}
+
+fn setup_with(msg: InstantiateMsg) -> MockDeps {
+    let mut deps = mock_dependencies();
+    instantiate(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), msg).unwrap();
+    deps
+}
+
+fn setup() -> MockDeps {
+    setup_with(instantiate_msg())
+}
+
+fn exec(deps: &mut MockDeps, sender: &str, msg: ExecuteMsg) -> Result<Response, ContractError> {
+    execute(deps.as_mut(), mock_env(), mock_info(sender, &[]), msg)
+}
+
+/// Executes `msg` `seconds` after the mock block time
+fn exec_after(deps: &mut MockDeps, seconds: u64, sender: &str, msg: ExecuteMsg) -> Result<Response, ContractError> {
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(seconds);
+    execute(deps.as_mut(), env, mock_info(sender, &[]), msg)
+}
+
+fn transfer(deps: &mut MockDeps, sender: &str, recipient: &str, amount: u128) -> Response {
+    exec(deps, sender, ExecuteMsg::Transfer { recipient: recipient.to_string(), amount: Uint128::new(amount) }).unwrap()
+}
+
+fn balance(deps: Deps, address: &str) -> Uint128 {
+    let res: BalanceResponse = from_json(query(deps, mock_env(), QueryMsg::Balance { address: address.to_string() }).unwrap()).unwrap();
+    res.balance
+}
+
+/// Checks the reflection bookkeeping every transfer path has to keep
+fn assert_accounting(deps: Deps) {
+    let cfg = CONFIG.load(deps.storage).unwrap();
+
+    let r_owned: Vec<(Addr, Uint128)> = ROWNED
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()
+        .unwrap();
+    let r_sum: Uint128 = r_owned.iter().map(|(_, r)| *r).sum();
+    assert_eq!(r_sum, cfg.r_total, "sum(ROWNED) != r_total");
+
+    let mut excluded_r = Uint128::zero();
+    let mut excluded_t = Uint128::zero();
+    for item in ISEXCLUDED.range(deps.storage, None, None, Order::Ascending) {
+        let (addr, excluded) = item.unwrap();
+        if excluded {
+            excluded_r += ROWNED.may_load(deps.storage, addr.clone()).unwrap().unwrap_or_default();
+            excluded_t += TOWNED.may_load(deps.storage, addr).unwrap().unwrap_or_default();
+        }
+    }
+    assert_eq!(excluded_r, cfg.excluded_r_total);
+    assert_eq!(excluded_t, cfg.excluded_t_total);
+
+    let info = TOKEN_INFO.load(deps.storage).unwrap();
+    assert_eq!(info.total_supply, cfg.t_total);
+
+    let burned = query_uint(deps, QueryMsg::TotalBurned {}).unwrap();
+    assert_eq!(burned, cfg.t_burn_total);
+    assert_eq!(cfg.t_total + cfg.t_burn_total, Uint128::new(1_000_000_000));
+}
+
+/// Asserts that the balances of `holders` add up to the supply, give or take
+/// a token of rounding per holder
+fn assert_balances_match_supply(deps: Deps, holders: &[&str]) {
+    let cfg = CONFIG.load(deps.storage).unwrap();
+    let total: Uint128 = holders.iter().map(|holder| balance(deps, holder)).sum();
+    let diff = if total > cfg.t_total { total - cfg.t_total } else { cfg.t_total - total };
+    assert!(
+        diff <= Uint128::new(holders.len() as u128),
+        "balances add up to {} for a supply of {}",
+        total,
+        cfg.t_total
+    );
+}
+
+fn query_uint(deps: Deps, msg: QueryMsg) -> StdResult<Uint128> {
+    let res: Uint128Response = from_json(&query(deps, mock_env(), msg)?)?;
+    Ok(res.ret)
+}
+
+#[test]
+fn reflection_from_token_values() {
+    let mut deps = setup();
+    let cfg = CONFIG.load(deps.as_ref().storage).unwrap();
+    let rate = cfg.r_total / cfg.t_total;
+
+    let reflection = |deps: Deps, t_amount: u128, deduct_transfer_fee: bool| {
+        query_uint(deps, QueryMsg::ReflectionFromToken { t_amount: Uint128::new(t_amount), deduct_transfer_fee }).unwrap()
+    };
+    assert_eq!(reflection(deps.as_ref(), 1_000, false), Uint128::new(1_000) * rate);
+    // 5% tax and 5% liquidity
+    assert_eq!(reflection(deps.as_ref(), 1_000, true), Uint128::new(900) * rate);
+
+    // the rate drops once fees have been reflected
+    transfer(&mut deps, OWNER, "alice", 10_000);
+    transfer(&mut deps, "alice", "bob", 1_000);
+    let cfg = CONFIG.load(deps.as_ref().storage).unwrap();
+    let new_rate = cfg.r_total / cfg.t_total;
+    assert!(new_rate < rate);
+    assert_eq!(reflection(deps.as_ref(), 1_000, false), Uint128::new(1_000) * new_rate);
+    assert_eq!(reflection(deps.as_ref(), 1_000, true), Uint128::new(900) * new_rate);
+}
+
+#[test]
+fn token_from_reflection_values() {
+    let mut deps = setup();
+    transfer(&mut deps, OWNER, "alice", 10_000);
+    transfer(&mut deps, "alice", "bob", 1_000);
+
+    let r_amount = query_uint(deps.as_ref(), QueryMsg::ReflectionFromToken { t_amount: Uint128::new(1_000), deduct_transfer_fee: false }).unwrap();
+    let t_amount = query_uint(deps.as_ref(), QueryMsg::TokenFromReflection { r_amount }).unwrap();
+    assert_eq!(t_amount, Uint128::new(1_000));
+
+    // a holder's balance is the token value of their reflections
+    let cfg = CONFIG.load(deps.as_ref().storage).unwrap();
+    let alice_r = ROWNED.load(deps.as_ref().storage, Addr::unchecked("alice")).unwrap();
+    let alice_t = query_uint(deps.as_ref(), QueryMsg::TokenFromReflection { r_amount: alice_r }).unwrap();
+    assert_eq!(alice_t, balance(deps.as_ref(), "alice"));
+
+    assert_eq!(query_uint(deps.as_ref(), QueryMsg::TokenFromReflection { r_amount: cfg.r_total }).unwrap(), cfg.t_total);
+}
+
+#[test]
+fn reflection_queries_reject_amounts_above_totals() {
+    let deps = setup();
+    let cfg = CONFIG.load(deps.as_ref().storage).unwrap();
+
+    let err = query_uint(deps.as_ref(), QueryMsg::ReflectionFromToken { t_amount: cfg.t_total + Uint128::new(1), deduct_transfer_fee: false }).unwrap_err();
+    assert!(err.to_string().contains("Amount must be less than supply"));
+    query_uint(deps.as_ref(), QueryMsg::ReflectionFromToken { t_amount: cfg.t_total, deduct_transfer_fee: true }).unwrap();
+
+    let err = query_uint(deps.as_ref(), QueryMsg::TokenFromReflection { r_amount: cfg.r_total + Uint128::new(1) }).unwrap_err();
+    assert!(err.to_string().contains("Amount must be less than total reflections"));
+}
+
+#[test]
+fn include_in_reward_keeps_supply() {
+    let mut deps = setup();
+    let contract = mock_env().contract.address.to_string();
+    let holders = [OWNER, "alice", "bob", "carol", contract.as_str()];
+
+    transfer(&mut deps, OWNER, "alice", 100_000_000);
+    transfer(&mut deps, OWNER, "bob", 500_000_000);
+    exec(&mut deps, OWNER, ExecuteMsg::ExcludeFromReward { address: Addr::unchecked("bob") }).unwrap();
+
+    // reflected to everyone but bob
+    transfer(&mut deps, "alice", "carol", 50_000);
+    let bob_balance = balance(deps.as_ref(), "bob");
+    assert_eq!(bob_balance, Uint128::new(500_000_000));
+
+    exec(&mut deps, OWNER, ExecuteMsg::IncludeInReward { address: Addr::unchecked("bob") }).unwrap();
+    assert_accounting(deps.as_ref());
+    assert_balances_match_supply(deps.as_ref(), &holders);
+    assert!(balance(deps.as_ref(), "bob") >= bob_balance - Uint128::new(1));
+
+    // bob shares in the reflections again
+    transfer(&mut deps, "alice", "carol", 50_000);
+    assert!(balance(deps.as_ref(), "bob") > bob_balance);
+    assert_accounting(deps.as_ref());
+    assert_balances_match_supply(deps.as_ref(), &holders);
+}
+
+/// Funds alice and bob and leaves bob and dave excluded from rewards
+fn setup_holders(deps: &mut MockDeps) {
+    transfer(deps, OWNER, "alice", 100_000_000);
+    transfer(deps, OWNER, "bob", 100_000_000);
+    exec(deps, OWNER, ExecuteMsg::ExcludeFromReward { address: Addr::unchecked("bob") }).unwrap();
+    exec(deps, OWNER, ExecuteMsg::ExcludeFromReward { address: Addr::unchecked("dave") }).unwrap();
+    assert_accounting(deps.as_ref());
+}
+
+/// Runs a taxed transfer through each of the four `_transfer_*` paths, checking
+/// the bookkeeping after every one. Kept small enough that the liquidity fees
+/// of two rounds stay below the liquify threshold.
+fn transfer_through_all_paths(deps: &mut MockDeps) {
+    // standard, to excluded, from excluded and both excluded
+    for (sender, recipient) in [("alice", "carol"), ("alice", "bob"), ("bob", "carol"), ("bob", "dave")].iter() {
+        transfer(deps, sender, recipient, 10_000);
+        assert_accounting(deps.as_ref());
+    }
+}
+
+#[test]
+fn transfer_paths_keep_excluded_totals() {
+    let mut deps = setup();
+    setup_holders(&mut deps);
+
+    // an excluded recipient is credited the amount less 5% tax and 5% liquidity
+    let before = CONFIG.load(deps.as_ref().storage).unwrap().excluded_t_total;
+    transfer(&mut deps, "alice", "bob", 10_000);
+    assert_eq!(balance(deps.as_ref(), "bob"), Uint128::new(100_009_000));
+    assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap().excluded_t_total, before + Uint128::new(9_000));
+    // and an excluded sender is debited the full amount
+    transfer(&mut deps, "bob", "carol", 10_000);
+    assert_eq!(balance(deps.as_ref(), "bob"), Uint128::new(99_999_000));
+    assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap().excluded_t_total, before - Uint128::new(1_000));
+    assert_accounting(deps.as_ref());
+
+    transfer_through_all_paths(&mut deps);
+    let cfg = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(cfg.excluded_t_total, balance(deps.as_ref(), "bob") + balance(deps.as_ref(), "dave"));
+    assert!(cfg.t_fee_total > Uint128::zero());
+
+    // the liquidity share lands on an excluded contract too
+    let contract = mock_env().contract.address;
+    exec(&mut deps, OWNER, ExecuteMsg::ExcludeFromReward { address: contract }).unwrap();
+    transfer_through_all_paths(&mut deps);
+}
+
+/// Leaves the store as v0.12.0 left it, with bob and dave excluded from rewards
+fn setup_v0_12() -> MockDeps {
+    let mut deps = mock_dependencies();
+    let storage = deps.as_mut().storage;
+    set_contract_version(storage, "crates.io:cw20-safemoon", "0.12.0").unwrap();
+
+    let t_total = Uint128::new(1_000_000_000);
+    let r_total = Uint128::MAX - (Uint128::MAX % t_total);
+    let rate = r_total / t_total;
+    Item::<ConfigV0_12>::new("config")
+        .save(
+            storage,
+            &ConfigV0_12 {
+                owner: Addr::unchecked(OWNER),
+                t_total,
+                r_total,
+                t_fee_total: Uint128::zero(),
+                tax_fee: 5,
+                previous_tax_fee: 5,
+                liquidity_fee: 3,
+                previous_liquidity_fee: 3,
+                pool_address: Addr::unchecked(POOL),
+                swap_and_liquidity_enabled: true,
+                max_tx_amount: Uint128::new(100_000),
+                num_tokens_sell_to_add_to_liquidity: Uint128::new(5_000),
+                denom: DENOM.to_string(),
+            },
+        )
+        .unwrap();
+
+    let holders = [(OWNER, 700_000_000u128, false), ("bob", 200_000_000, true), ("carol", 60_000_000, false), ("dave", 40_000_000, true)];
+    for (holder, amount, excluded) in holders.iter() {
+        let addr = Addr::unchecked(*holder);
+        ROWNED.save(storage, addr.clone(), &(Uint128::new(*amount) * rate)).unwrap();
+        if *excluded {
+            TOWNED.save(storage, addr.clone(), &Uint128::new(*amount)).unwrap();
+            ISEXCLUDED.save(storage, addr, &true).unwrap();
+        }
+    }
+    // carol was excluded once and included again
+    ISEXCLUDED.save(storage, Addr::unchecked("carol"), &false).unwrap();
+    deps
+}
+
+#[test]
+fn migrate_from_v0_12() {
+    let mut deps = setup_v0_12();
+    let old = Item::<ConfigV0_12>::new("config").load(deps.as_ref().storage).unwrap();
+    migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+
+    let version = get_contract_version(deps.as_ref().storage).unwrap();
+    assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+
+    let cfg = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(cfg.excluded_t_total, Uint128::new(240_000_000));
+    assert_eq!(cfg.excluded_r_total, Uint128::new(240_000_000) * (old.r_total / old.t_total));
+    assert_eq!(cfg.t_total, old.t_total);
+    assert_eq!(cfg.r_total, old.r_total);
+    assert_eq!(cfg.max_tx_amount, old.max_tx_amount);
+    assert_eq!(cfg.max_liquify_amount, old.max_tx_amount);
+    assert_eq!(cfg.max_total_fee, 2_500);
+    // whole percents become basis points
+    // and apply to every kind of transfer
+    assert_eq!(cfg.buy_fee, fee(500, 300, 0));
+    assert_eq!(cfg.sell_fee, fee(500, 300, 0));
+    assert_eq!(cfg.transfer_fee, fee(500, 300, 0));
+    assert_eq!(balance(deps.as_ref(), "bob"), Uint128::new(200_000_000));
+    assert_eq!(balance(deps.as_ref(), "carol"), Uint128::new(60_000_000));
+    // the v0.12.x pool is the primary pair
+    assert_eq!(amm_pairs(deps.as_ref()), vec![AmmPairInfo { address: old.pool_address.clone(), pair: pair(None, None) }]);
+
+    // running it again is a no-op
+    migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap();
+    assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap(), cfg);
+}
+
+#[test]
+fn migrate_rejects_other_contracts_and_downgrades() {
+    let mut deps = setup_v0_12();
+    set_contract_version(deps.as_mut().storage, "crates.io:cw20-base", "0.12.0").unwrap();
+    let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+    assert_eq!(err, ContractError::CannotMigrate { previous_contract: "crates.io:cw20-base".to_string() });
+
+    set_contract_version(deps.as_mut().storage, "crates.io:cw20-safemoon", "99.0.0").unwrap();
+    let err = migrate(deps.as_mut(), mock_env(), MigrateMsg {}).unwrap_err();
+    assert_eq!(err, ContractError::CannotMigrateVersion { previous_version: "99.0.0".to_string() });
+}
+
+fn propose(deps: &mut MockDeps, owner: &str, expiry: Option<Expiration>) -> Result<Response, ContractError> {
+    exec(deps, OWNER, ExecuteMsg::ProposeNewOwner { owner: owner.to_string(), expiry })
+}
+
+fn config(deps: Deps) -> ConfigResponse {
+    from_json(query(deps, mock_env(), QueryMsg::Config {}).unwrap()).unwrap()
+}
+
+fn owner(deps: Deps) -> Option<Addr> {
+    config(deps).owner
+}
+
+fn ownership_proposal(deps: Deps) -> OwnershipProposalResponse {
+    from_json(query(deps, mock_env(), QueryMsg::OwnershipProposal {}).unwrap()).unwrap()
+}
+
+#[test]
+fn ownership_is_transferred_once_accepted() {
+    let mut deps = setup();
+    let expiry = Some(Expiration::AtHeight(mock_env().block.height + 10));
+
+    let err = exec(&mut deps, "bob", ExecuteMsg::ProposeNewOwner { owner: "bob".to_string(), expiry: None }).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    propose(&mut deps, "alice", expiry).unwrap();
+    assert_eq!(ownership_proposal(deps.as_ref()), OwnershipProposalResponse { owner: Some(Addr::unchecked("alice")), expiry });
+
+    // the current owner keeps every right until the proposal is accepted
+    assert_eq!(owner(deps.as_ref()), Some(Addr::unchecked(OWNER)));
+    let err = exec(&mut deps, "bob", ExecuteMsg::AcceptOwnership {}).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    exec(&mut deps, "alice", ExecuteMsg::AcceptOwnership {}).unwrap();
+    assert_eq!(owner(deps.as_ref()), Some(Addr::unchecked("alice")));
+    assert_eq!(ownership_proposal(deps.as_ref()), OwnershipProposalResponse { owner: None, expiry: None });
+    let err = exec(&mut deps, "alice", ExecuteMsg::AcceptOwnership {}).unwrap_err();
+    assert_eq!(err, ContractError::NoOwnershipProposal {});
+
+    // the previous owner is an ordinary account now
+    let err = propose(&mut deps, "bob", None).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+}
+
+#[test]
+fn ownership_proposal_can_be_cancelled() {
+    let mut deps = setup();
+    let err = exec(&mut deps, OWNER, ExecuteMsg::CancelOwnershipProposal {}).unwrap_err();
+    assert_eq!(err, ContractError::NoOwnershipProposal {});
+
+    propose(&mut deps, "alice", None).unwrap();
+    let err = exec(&mut deps, "alice", ExecuteMsg::CancelOwnershipProposal {}).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    exec(&mut deps, OWNER, ExecuteMsg::CancelOwnershipProposal {}).unwrap();
+
+    let err = exec(&mut deps, "alice", ExecuteMsg::AcceptOwnership {}).unwrap_err();
+    assert_eq!(err, ContractError::NoOwnershipProposal {});
+    assert_eq!(owner(deps.as_ref()), Some(Addr::unchecked(OWNER)));
+}
+
+#[test]
+fn ownership_proposal_expires() {
+    let mut deps = setup();
+    let height = mock_env().block.height;
+
+    let err = propose(&mut deps, "alice", Some(Expiration::AtHeight(height))).unwrap_err();
+    assert_eq!(err, ContractError::OwnershipProposalExpired {});
+
+    propose(&mut deps, "alice", Some(Expiration::AtHeight(height + 10))).unwrap();
+    let mut env = mock_env();
+    env.block.height += 10;
+    let err = execute(deps.as_mut(), env, mock_info("alice", &[]), ExecuteMsg::AcceptOwnership {}).unwrap_err();
+    assert_eq!(err, ContractError::OwnershipProposalExpired {});
+    assert_eq!(owner(deps.as_ref()), Some(Addr::unchecked(OWNER)));
+
+    // a new proposal replaces the expired one
+    propose(&mut deps, "alice", None).unwrap();
+    exec(&mut deps, "alice", ExecuteMsg::AcceptOwnership {}).unwrap();
+    assert_eq!(owner(deps.as_ref()), Some(Addr::unchecked("alice")));
+}
+
+#[test]
+fn renounce_ownership_freezes_admin_messages() {
+    let mut deps = setup();
+    transfer(&mut deps, OWNER, "alice", 1_000);
+    propose(&mut deps, "alice", None).unwrap();
+    exec(&mut deps, OWNER, ExecuteMsg::SetTransferFee { tax_fee: 100, liquidity_fee: 0, burn_fee: 0 }).unwrap();
+    exec(&mut deps, OWNER, ExecuteMsg::SetTransferFee { tax_fee: 200, liquidity_fee: 0, burn_fee: 0 }).unwrap();
+    for role in [Role::FeeManager, Role::LiquidityManager, Role::ExclusionManager].iter() {
+        exec(&mut deps, OWNER, ExecuteMsg::GrantRole { address: "bob".to_string(), role: role.clone() }).unwrap();
+    }
+    assert!(!config(deps.as_ref()).frozen);
+
+    let err = exec(&mut deps, "alice", ExecuteMsg::RenounceOwnership {}).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    exec(&mut deps, OWNER, ExecuteMsg::RenounceOwnership {}).unwrap();
+    let cfg = config(deps.as_ref());
+    assert!(cfg.frozen);
+    assert_eq!(cfg.owner, None);
+
+    // the pending proposal went with it
+    let err = exec(&mut deps, "alice", ExecuteMsg::AcceptOwnership {}).unwrap_err();
+    assert_eq!(err, ContractError::NoOwnershipProposal {});
+
+    let alice = Addr::unchecked("alice");
+    let admin_msgs = vec![
+        ExecuteMsg::ProposeNewOwner { owner: "alice".to_string(), expiry: None },
+        ExecuteMsg::GrantRole { address: "alice".to_string(), role: Role::FeeManager },
+        ExecuteMsg::RevokeRole { address: "bob".to_string(), role: Role::FeeManager },
+        ExecuteMsg::CancelOwnershipProposal {},
+        ExecuteMsg::RenounceOwnership {},
+        ExecuteMsg::UpdatePool { address: Addr::unchecked("other_pool"), dex: None },
+        ExecuteMsg::AddAmmPair { address: "other_pool".to_string(), dex: Dex::Wasmswap, buy_fee: None, sell_fee: None },
+        ExecuteMsg::RemoveAmmPair { address: "other_pool".to_string() },
+        ExecuteMsg::ExcludeFromReward { address: alice.clone() },
+        ExecuteMsg::IncludeInReward { address: alice.clone() },
+        ExecuteMsg::ExcludeFromFee { address: alice.clone() },
+        ExecuteMsg::IncludeInFee { address: alice },
+        ExecuteMsg::SetBuyFee { tax_fee: 100, liquidity_fee: 100, burn_fee: 0 },
+        ExecuteMsg::SetSellFee { tax_fee: 100, liquidity_fee: 100, burn_fee: 0 },
+        ExecuteMsg::SetTransferFee { tax_fee: 100, liquidity_fee: 100, burn_fee: 0 },
+        ExecuteMsg::SetMaxTxPercent { percent: 1 },
+        ExecuteMsg::SetSwapAndLiquifyEnabled { enabled: false },
+        ExecuteMsg::SetNumTokensSellToAddToLiquidity { amount: Uint128::new(1_000) },
+        ExecuteMsg::SetMaxLiquifyAmount { amount: Uint128::new(50_000) },
+        ExecuteMsg::SetLiquifyInterval { interval: Duration::Time(60) },
+        ExecuteMsg::SetLiquifySlippage { max_slippage_bps: 50, swap_expiration: None },
+        ExecuteMsg::SetLpPolicy { policy: LpPolicy::Burn { dead_address: Addr::unchecked("dead") } },
+        ExecuteMsg::SetWithdrawCaps { caps: WithdrawCaps { period: Duration::Time(60), lp_amount: Uint128::new(1), quote_amount: Uint128::new(1) } },
+        ExecuteMsg::WithdrawLp { amount: Uint128::new(1), recipient: None },
+        ExecuteMsg::WithdrawNative { amount: Uint128::new(1), recipient: None },
+        ExecuteMsg::ExecutePendingChange { id: 1 },
+        ExecuteMsg::CancelPendingChange { id: 2 },
+    ];
+    for msg in admin_msgs {
+        // role holders lose their rights along with the owner
+        for sender in [OWNER, "alice", "bob"].iter() {
+            // past the timelock, so only the missing owner stops a queued change
+            let err = exec_after(&mut deps, 60, sender, msg.clone()).unwrap_err();
+            assert_eq!(err, ContractError::Frozen {}, "{:?} from {}", msg, sender);
+        }
+    }
+
+    // token transfers keep working
+    transfer(&mut deps, "alice", "bob", 100);
+    assert!(config(deps.as_ref()).frozen);
+}
+
+fn roles(deps: Deps, address: &str) -> Vec<Role> {
+    let res: RolesResponse = from_json(query(deps, mock_env(), QueryMsg::Roles { address: address.to_string() }).unwrap()).unwrap();
+    res.roles
+}
+
+/// Admin messages each role may send, in an order that lets them all succeed
+fn role_messages(role: &Role) -> Vec<ExecuteMsg> {
+    let carol = Addr::unchecked("carol");
+    match role {
+        Role::FeeManager => vec![
+            ExecuteMsg::SetBuyFee { tax_fee: 400, liquidity_fee: 400, burn_fee: 0 },
+            ExecuteMsg::SetSellFee { tax_fee: 400, liquidity_fee: 400, burn_fee: 0 },
+            ExecuteMsg::SetTransferFee { tax_fee: 400, liquidity_fee: 400, burn_fee: 0 },
+            ExecuteMsg::SetMaxTxPercent { percent: 1 },
+        ],
+        Role::LiquidityManager => vec![
+            ExecuteMsg::UpdatePool { address: Addr::unchecked("other_pool"), dex: None },
+            ExecuteMsg::AddAmmPair { address: "flipped_pool".to_string(), dex: Dex::Wasmswap, buy_fee: None, sell_fee: None },
+            ExecuteMsg::RemoveAmmPair { address: "other_pool".to_string() },
+            ExecuteMsg::SetSwapAndLiquifyEnabled { enabled: false },
+            ExecuteMsg::SetNumTokensSellToAddToLiquidity { amount: Uint128::new(1_000) },
+            ExecuteMsg::SetMaxLiquifyAmount { amount: Uint128::new(50_000) },
+            ExecuteMsg::SetLiquifyInterval { interval: Duration::Time(60) },
+            ExecuteMsg::SetLiquifySlippage { max_slippage_bps: 50, swap_expiration: None },
+            ExecuteMsg::SetLpPolicy { policy: LpPolicy::Hold },
+            ExecuteMsg::SetWithdrawCaps { caps: WithdrawCaps { period: Duration::Time(60), lp_amount: Uint128::new(1), quote_amount: Uint128::new(1) } },
+            ExecuteMsg::WithdrawLp { amount: Uint128::new(1), recipient: None },
+        ],
+        Role::ExclusionManager => vec![
+            ExecuteMsg::ExcludeFromReward { address: carol.clone() },
+            ExecuteMsg::IncludeInReward { address: carol.clone() },
+            ExecuteMsg::ExcludeFromFee { address: carol.clone() },
+            ExecuteMsg::IncludeInFee { address: carol },
+        ],
+        Role::Keeper => vec![
+            ExecuteMsg::Liquify { amount: None },
+        ],
+    }
+}
+
+#[test]
+fn roles_are_limited_to_their_own_messages() {
+    let all_roles = [Role::FeeManager, Role::LiquidityManager, Role::ExclusionManager, Role::Keeper];
+    for role in all_roles.iter() {
+        let mut deps = setup_liquify();
+        AMM_PAIRS.save(deps.as_mut().storage, Addr::unchecked("other_pool"), &pair(None, None)).unwrap();
+        exec(&mut deps, OWNER, ExecuteMsg::GrantRole { address: "manager".to_string(), role: role.clone() }).unwrap();
+        assert_eq!(roles(deps.as_ref(), "manager"), vec![role.clone()]);
+
+        for other in all_roles.iter().filter(|other| *other != role) {
+            for msg in role_messages(other) {
+                let err = exec(&mut deps, "manager", msg.clone()).unwrap_err();
+                assert_eq!(err, ContractError::Unauthorized {}, "{} sent {:?}", role, msg);
+            }
+        }
+        for msg in role_messages(role) {
+            match exec(&mut deps, "manager", msg.clone()) {
+                Ok(_) => {}
+                Err(err) => panic!("{} sent {:?}: {}", role, msg, err),
+            }
+        }
+
+        // ownership stays with the owner
+        let err = exec(&mut deps, "manager", ExecuteMsg::GrantRole { address: "manager".to_string(), role: Role::FeeManager }).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+        let err = exec(&mut deps, "manager", ExecuteMsg::ProposeNewOwner { owner: "manager".to_string(), expiry: None }).unwrap_err();
+        assert_eq!(err, ContractError::Unauthorized {});
+
+        exec(&mut deps, OWNER, ExecuteMsg::RevokeRole { address: "manager".to_string(), role: role.clone() }).unwrap();
+        assert_eq!(roles(deps.as_ref(), "manager"), vec![]);
+        for msg in role_messages(role) {
+            let err = exec(&mut deps, "manager", msg.clone()).unwrap_err();
+            assert_eq!(err, ContractError::Unauthorized {}, "{:?} after revoke", msg);
+        }
+    }
+}
+
+fn pending_changes(deps: Deps) -> Vec<ConfigChange> {
+    let res: PendingChangesResponse = from_json(query(deps, mock_env(), QueryMsg::PendingChanges {}).unwrap()).unwrap();
+    res.changes.into_iter().map(|pending| pending.change).collect()
+}
+
+#[test]
+fn config_changes_wait_for_the_timelock() {
+    let mut deps = setup();
+    mock_pool(&mut deps);
+    exec(&mut deps, OWNER, ExecuteMsg::GrantRole { address: "fees".to_string(), role: Role::FeeManager }).unwrap();
+    exec(&mut deps, OWNER, ExecuteMsg::GrantRole { address: "liquidity".to_string(), role: Role::LiquidityManager }).unwrap();
+
+    exec(&mut deps, "fees", ExecuteMsg::SetTransferFee { tax_fee: 200, liquidity_fee: 500, burn_fee: 0 }).unwrap();
+    exec(&mut deps, "liquidity", ExecuteMsg::UpdatePool { address: Addr::unchecked("other_pool"), dex: None }).unwrap();
+    assert_eq!(
+        pending_changes(deps.as_ref()),
+        vec![ConfigChange::Fee { kind: TransferKind::Transfer, fee: fee(200, 500, 0) }, ConfigChange::PoolAddress { address: Addr::unchecked("other_pool"), dex: Dex::Wasmswap }]
+    );
+    assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap().transfer_fee, fee(500, 500, 0));
+
+    let err = exec_after(&mut deps, 59, "fees", ExecuteMsg::ExecutePendingChange { id: 1 }).unwrap_err();
+    assert!(matches!(err, ContractError::Timelocked { .. }));
+    // each change is executed by the role that queued it
+    let err = exec_after(&mut deps, 60, "liquidity", ExecuteMsg::ExecutePendingChange { id: 1 }).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    exec_after(&mut deps, 60, "fees", ExecuteMsg::ExecutePendingChange { id: 1 }).unwrap();
+    assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap().transfer_fee, fee(200, 500, 0));
+
+    let err = exec(&mut deps, "fees", ExecuteMsg::CancelPendingChange { id: 2 }).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+    exec(&mut deps, "liquidity", ExecuteMsg::CancelPendingChange { id: 2 }).unwrap();
+    assert_eq!(pending_changes(deps.as_ref()), vec![]);
+    exec_after(&mut deps, 60, "liquidity", ExecuteMsg::ExecutePendingChange { id: 2 }).unwrap_err();
+    assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap().pool_address, Addr::unchecked(POOL));
+}
+
+#[test]
+fn instantiate_rejects_fees_above_the_cap() {
+    let mut msg = instantiate_msg();
+    msg.sell_fee = fee(2_000, 501, 0);
+    let err = instantiate(mock_dependencies().as_mut(), mock_env(), mock_info(OWNER, &[]), msg.clone()).unwrap_err();
+    assert_eq!(err, ContractError::FeeTooHigh {});
+
+    msg.sell_fee = fee(2_000, 500, 0);
+    instantiate(mock_dependencies().as_mut(), mock_env(), mock_info(OWNER, &[]), msg.clone()).unwrap();
+
+    // the cap itself cannot go above 100%
+    msg.max_total_fee = 10_001;
+    let err = instantiate(mock_dependencies().as_mut(), mock_env(), mock_info(OWNER, &[]), msg).unwrap_err();
+    assert_eq!(err, ContractError::FeeTooHigh {});
+}
+
+#[test]
+fn fee_setters_respect_the_cap() {
+    let mut deps = setup();
+    let too_high = vec![
+        ExecuteMsg::SetBuyFee { tax_fee: 2_001, liquidity_fee: 500, burn_fee: 0 },
+        ExecuteMsg::SetSellFee { tax_fee: 0, liquidity_fee: 2_501, burn_fee: 0 },
+        ExecuteMsg::SetTransferFee { tax_fee: 10_000, liquidity_fee: 0, burn_fee: 0 },
+    ];
+    for msg in too_high {
+        let err = exec(&mut deps, OWNER, msg).unwrap_err();
+        assert_eq!(err, ContractError::FeeTooHigh {});
+    }
+
+    // the cap applies to each profile on its own
+    exec(&mut deps, OWNER, ExecuteMsg::SetBuyFee { tax_fee: 2_000, liquidity_fee: 500, burn_fee: 0 }).unwrap();
+    exec(&mut deps, OWNER, ExecuteMsg::SetSellFee { tax_fee: 0, liquidity_fee: 2_500, burn_fee: 0 }).unwrap();
+    exec_after(&mut deps, 60, OWNER, ExecuteMsg::ExecutePendingChange { id: 1 }).unwrap();
+    exec_after(&mut deps, 60, OWNER, ExecuteMsg::ExecutePendingChange { id: 2 }).unwrap();
+
+    let cfg = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(cfg.buy_fee, fee(2_000, 500, 0));
+    assert_eq!(cfg.sell_fee, fee(0, 2_500, 0));
+}
+
+#[test]
+fn fees_are_taken_in_basis_points() {
+    let mut msg = instantiate_msg();
+    // 0.5% tax and 0.25% liquidity
+    msg.transfer_fee = fee(50, 25, 0);
+    let mut deps = setup_with(msg);
+    transfer(&mut deps, OWNER, "alice", 100_000);
+    exec(&mut deps, OWNER, ExecuteMsg::ExcludeFromReward { address: Addr::unchecked("bob") }).unwrap();
+
+    transfer(&mut deps, "alice", "bob", 10_000);
+    assert_eq!(balance(deps.as_ref(), "bob"), Uint128::new(9_925));
+    let cfg = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(cfg.t_fee_total, Uint128::new(50));
+    let contract = mock_env().contract.address.to_string();
+    assert_eq!(balance(deps.as_ref(), &contract), Uint128::new(25));
+
+    // fractions of a token round down
+    exec(&mut deps, OWNER, ExecuteMsg::SetTransferFee { tax_fee: 1, liquidity_fee: 25, burn_fee: 0 }).unwrap();
+    exec_after(&mut deps, 60, OWNER, ExecuteMsg::ExecutePendingChange { id: 1 }).unwrap();
+    transfer(&mut deps, "alice", "bob", 9_999);
+    assert_eq!(balance(deps.as_ref(), "bob"), Uint128::new(9_925 + 9_999 - 24));
+    assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap().t_fee_total, Uint128::new(50));
+    assert_accounting(deps.as_ref());
+}
+
+#[test]
+fn fee_profile_follows_the_transfer_direction() {
+    let mut msg = instantiate_msg();
+    msg.buy_fee = fee(100, 200, 0);
+    msg.sell_fee = fee(300, 400, 0);
+    msg.transfer_fee = fee(500, 600, 0);
+    let mut deps = setup_with(msg);
+    let contract = mock_env().contract.address.to_string();
+    transfer(&mut deps, OWNER, POOL, 100_000);
+    transfer(&mut deps, OWNER, "alice", 100_000);
+    // exact amounts without reflections
+    for holder in [POOL, "alice", "bob", contract.as_str()].iter() {
+        exec(&mut deps, OWNER, ExecuteMsg::ExcludeFromReward { address: Addr::unchecked(*holder) }).unwrap();
+    }
+
+    // buy from the pool
+    transfer(&mut deps, POOL, "bob", 10_000);
+    assert_eq!(balance(deps.as_ref(), "bob"), Uint128::new(10_000 - 100 - 200));
+    assert_eq!(balance(deps.as_ref(), &contract), Uint128::new(200));
+    assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap().t_fee_total, Uint128::new(100));
+
+    // sell into the pool
+    transfer(&mut deps, "alice", POOL, 10_000);
+    assert_eq!(balance(deps.as_ref(), POOL), Uint128::new(100_000 - 10_000 + 10_000 - 300 - 400));
+    assert_eq!(balance(deps.as_ref(), &contract), Uint128::new(200 + 400));
+    assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap().t_fee_total, Uint128::new(100 + 300));
+
+    // wallet to wallet
+    transfer(&mut deps, "alice", "bob", 10_000);
+    assert_eq!(balance(deps.as_ref(), "bob"), Uint128::new(9_700 + 10_000 - 500 - 600));
+    assert_eq!(balance(deps.as_ref(), &contract), Uint128::new(200 + 400 + 600));
+    assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap().t_fee_total, Uint128::new(100 + 300 + 500));
+    assert_accounting(deps.as_ref());
+}
+
+#[test]
+fn burn_fee_shrinks_supply_on_every_path() {
+    let mut msg = instantiate_msg();
+    msg.transfer_fee = fee(500, 500, 200);
+    let mut deps = setup_with(msg);
+    setup_holders(&mut deps);
+
+    // bob is excluded, so he is credited exactly the amount less 12% of fees
+    transfer(&mut deps, "alice", "bob", 10_000);
+    assert_eq!(balance(deps.as_ref(), "bob"), Uint128::new(100_000_000 + 8_800));
+    let cfg = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(cfg.t_burn_total, Uint128::new(200));
+    assert_eq!(cfg.t_total, Uint128::new(1_000_000_000 - 200));
+    assert_accounting(deps.as_ref());
+
+    // 2% of four more 10k transfers
+    transfer_through_all_paths(&mut deps);
+    let cfg = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(cfg.t_burn_total, Uint128::new(1_000));
+    assert_eq!(cfg.t_total, Uint128::new(1_000_000_000 - 1_000));
+    let contract = mock_env().contract.address.to_string();
+    assert_balances_match_supply(deps.as_ref(), &[OWNER, "alice", "bob", "carol", "dave", contract.as_str()]);
+}
+
+#[test]
+fn marketing_fee_accrues_to_contract_on_every_path() {
+    let mut msg = instantiate_msg();
+    msg.transfer_fee = fee(500, 300, 200);
+    msg.marketing_fee = 100;
+    msg.marketing_address = Some("treasury".to_string());
+    let mut deps = setup_with(msg);
+    let contract = mock_env().contract.address;
+    setup_holders(&mut deps);
+
+    // bob is excluded, so he is credited exactly the amount less 11% of fees
+    let held = balance(deps.as_ref(), contract.as_str());
+    transfer(&mut deps, "alice", "bob", 10_000);
+    assert_eq!(balance(deps.as_ref(), "bob"), Uint128::new(100_000_000 + 8_900));
+    assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap().marketing_tokens, Uint128::new(100));
+    assert!(balance(deps.as_ref(), contract.as_str()) >= held + Uint128::new(400));
+    assert_accounting(deps.as_ref());
+
+    // 1% of four more 10k transfers, on top of 3% liquidity
+    transfer_through_all_paths(&mut deps);
+    let cfg = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(cfg.marketing_tokens, Uint128::new(500));
+
+    exec(&mut deps, OWNER, ExecuteMsg::ExcludeFromReward { address: contract.clone() }).unwrap();
+    let held = balance(deps.as_ref(), contract.as_str());
+    transfer_through_all_paths(&mut deps);
+    let cfg = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(cfg.marketing_tokens, Uint128::new(900));
+    assert_eq!(balance(deps.as_ref(), contract.as_str()), held + Uint128::new(1_600));
+}
+
+/// Answers the wasmswap Info query for `POOL`, with juno as token1
+/// Wasmswap Info of a pool pairing `denom` with this token, `denom` being
+/// token1 unless `flipped`
+fn pool_info(denom: &str, flipped: bool) -> InfoResponse {
+    let native = (Uint128::new(1_000_000), Denom::Native(denom.to_string()));
+    let token = (Uint128::new(10_000_000), Denom::Cw20(mock_env().contract.address));
+    let ((token1_reserve, token1_denom), (token2_reserve, token2_denom)) = if flipped { (token, native) } else { (native, token) };
+    InfoResponse {
+        token1_reserve,
+        token1_denom,
+        token2_reserve,
+        token2_denom,
+        lp_token_supply: Uint128::new(1_000_000),
+        lp_token_address: "lp".to_string(),
+    }
+}
+
+/// Mocks POOL and "other_pool" with the native denom as token1, "flipped_pool"
+/// with it as token2 and "foreign_pool" pairing another denom. The contract
+/// holds 1M of their "lp" token
+fn mock_pool(deps: &mut MockDeps) {
+    mock_pool_holding(deps, 1_000_000);
+}
+
+fn mock_pool_holding(deps: &mut MockDeps, lp_balance: u128) {
+    deps.querier.update_wasm(move |query| match query {
+        WasmQuery::Smart { contract_addr, msg } if contract_addr == "lp" => match from_json(msg).unwrap() {
+            Cw20QueryMsg::Balance { .. } => {
+                SystemResult::Ok(ContractResult::Ok(to_json_binary(&BalanceResponse { balance: Uint128::new(lp_balance) }).unwrap()))
+            }
+            _ => SystemResult::Err(SystemError::UnsupportedRequest { kind: "cw20".to_string() }),
+        },
+        WasmQuery::Smart { contract_addr, .. } => {
+            let info = match contract_addr.as_str() {
+                POOL | "other_pool" => pool_info(DENOM, false),
+                "flipped_pool" => pool_info(DENOM, true),
+                "foreign_pool" => pool_info("uatom", false),
+                _ => return SystemResult::Err(SystemError::NoSuchContract { addr: contract_addr.clone() }),
+            };
+            SystemResult::Ok(ContractResult::Ok(to_json_binary(&info).unwrap()))
+        }
+        _ => SystemResult::Err(SystemError::UnsupportedRequest { kind: "wasm".to_string() }),
+    });
+    CONFIG.update(deps.as_mut().storage, |mut cfg| -> StdResult<_> {
+        cfg.denom_side = Some(TokenSelect::Token1);
+        Ok(cfg)
+    }).unwrap();
+}
+
+/// Returns the input and minimum output of the pool swap in `res`, if any
+fn liquify_swap(res: &Response) -> Option<(Uint128, Uint128)> {
+    res.messages.iter().find_map(|msg| match &msg.msg {
+        CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, .. }) if contract_addr == POOL => {
+            match from_json(msg).unwrap() {
+                WasmswapExecuteMsg::Swap { input_amount, min_output, .. } => Some((input_amount, min_output)),
+                _ => None,
+            }
+        }
+        _ => None,
+    })
+}
+
+/// Replies to the liquify swap, as if it succeeded
+fn swap_reply(deps: &mut MockDeps) -> Response {
+    reply(deps.as_mut(), mock_env(), Reply {
+        id: LIQUIFY_SWAP_REPLY_ID,
+        result: SubMsgResult::Ok(SubMsgResponse { events: vec![], data: None }),
+    }).unwrap()
+}
+
+fn bank_sends(res: &Response) -> Vec<(String, Uint128)> {
+    res.messages.iter().filter_map(|msg| match &msg.msg {
+        CosmosMsg::Bank(BankMsg::Send { to_address, amount }) => Some((to_address.clone(), amount[0].amount)),
+        _ => None,
+    }).collect()
+}
+
+#[test]
+fn marketing_fees_wait_for_a_treasury() {
+    let mut msg = instantiate_msg();
+    msg.transfer_fee = fee(0, 400, 0);
+    msg.marketing_fee = 100;
+    let mut deps = setup_with(msg);
+    mock_pool(&mut deps);
+    let contract = mock_env().contract.address;
+    transfer(&mut deps, OWNER, "alice", 1_000_000);
+    exec(&mut deps, OWNER, ExecuteMsg::ExcludeFromReward { address: contract.clone() }).unwrap();
+
+    // 4k of liquidity and 1k of marketing fees
+    transfer(&mut deps, "alice", "bob", 100_000);
+    assert_eq!(balance(deps.as_ref(), contract.as_str()), Uint128::new(5_000));
+    assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap().marketing_tokens, Uint128::new(1_000));
+
+    // the contract holds the threshold, but the marketing share is not for sale
+    let res = transfer(&mut deps, "alice", "bob", 30_000);
+    assert_eq!(liquify_swap(&res), None);
+    assert_eq!(balance(deps.as_ref(), contract.as_str()), Uint128::new(6_500));
+    assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap().marketing_tokens, Uint128::new(1_300));
+
+    // 5.2k of liquidity fees: the threshold is sold as liquidity only
+    let res = transfer(&mut deps, "alice", "bob", 10_000);
+    assert_eq!(liquify_swap(&res).unwrap().0, Uint128::new(2_500));
+    assert_eq!(bank_sends(&res), vec![]);
+    assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap().marketing_tokens, Uint128::new(1_400));
+
+    // once a treasury is set it gets the whole 1.4k built up so far
+    exec(&mut deps, OWNER, ExecuteMsg::SetMarketingAddress { address: Some("treasury".to_string()) }).unwrap();
+    let res = transfer(&mut deps, "alice", "bob", 10_000);
+    // 1.4k of marketing plus half of the remaining 3.6k
+    assert_eq!(liquify_swap(&res).unwrap().0, Uint128::new(1_400 + 1_800));
+    assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap().marketing_tokens, Uint128::new(100));
+    deps.querier.update_balance(contract, coins(320, DENOM));
+    let res = swap_reply(&mut deps);
+    assert_eq!(bank_sends(&res), vec![("treasury".to_string(), Uint128::new(140))]);
+}
+
+#[test]
+fn fee_exempt_transfer_leaves_config_untouched() {
+    let mut msg = instantiate_msg();
+    msg.transfer_fee = fee(500, 300, 200);
+    msg.marketing_fee = 100;
+    let mut deps = setup_with(msg);
+    transfer(&mut deps, OWNER, "alice", 100_000);
+    exec(&mut deps, OWNER, ExecuteMsg::ExcludeFromFee { address: Addr::unchecked("alice") }).unwrap();
+
+    let before = CONFIG.load(deps.as_ref().storage).unwrap();
+    transfer(&mut deps, "alice", "bob", 10_000);
+    assert_eq!(balance(deps.as_ref(), "bob"), Uint128::new(10_000));
+    assert_eq!(CONFIG.load(deps.as_ref().storage).unwrap(), before);
+
+    // the next taxed transfer pays the full fees
+    transfer(&mut deps, "bob", "carol", 10_000);
+    assert_eq!(balance(deps.as_ref(), "carol"), Uint128::new(8_900));
+    let cfg = CONFIG.load(deps.as_ref().storage).unwrap();
+    assert_eq!(cfg.transfer_fee, fee(500, 300, 200));
+    assert_eq!(cfg.marketing_fee, 100);
+    assert_eq!(cfg.t_burn_total, Uint128::new(200));
+}
+
+/// Sets up a contract holding exactly the liquify threshold, with alice
+/// funded and a mocked pool
+fn setup_liquify() -> MockDeps {
+    let mut deps = setup();
+    mock_pool(&mut deps);
+    let contract = mock_env().contract.address.to_string();
+    transfer(&mut deps, OWNER, "alice", 1_000_000);
+    transfer(&mut deps, OWNER, &contract, 5_000);
+    deps
+}
+
+fn liquifies(res: &Response) -> bool {
+    liquify_swap(res).is_some()
+}
+
+#[test]
+fn every_transfer_entry_point_liquifies() {
+    let allow = |deps: &mut MockDeps| {
+        exec(deps, "alice", ExecuteMsg::IncreaseAllowance {
+            spender: "spender".to_string(),
+            amount: Uint128::new(1_000),
+            expires: None,
+        }).unwrap();
+    };
+    let amount = Uint128::new(1_000);
+
+    let mut deps = setup_liquify();
+    let res = exec(&mut deps, "alice", ExecuteMsg::Transfer { recipient: "carol".to_string(), amount }).unwrap();
+    assert!(liquifies(&res));
+
+    let mut deps = setup_liquify();
+    allow(&mut deps);
+    let res = exec(&mut deps, "spender", ExecuteMsg::TransferFrom {
+        owner: "alice".to_string(),
+        recipient: "carol".to_string(),
+        amount,
+    }).unwrap();
+    assert!(liquifies(&res));
+
+    let mut deps = setup_liquify();
+    let res = exec(&mut deps, "alice", ExecuteMsg::Send {
+        contract: "receiver".to_string(),
+        amount,
+        msg: Binary::default(),
+    }).unwrap();
+    assert!(liquifies(&res));
+
+    let mut deps = setup_liquify();
+    allow(&mut deps);
+    let res = exec(&mut deps, "spender", ExecuteMsg::SendFrom {
+        owner: "alice".to_string(),
+        contract: "receiver".to_string(),
+        amount,
+        msg: Binary::default(),
+    }).unwrap();
+    assert!(liquifies(&res));
+}
+
+#[test]
+fn transfer_from_contract_does_not_liquify() {
+    let mut deps = setup_liquify();
+    let contract = mock_env().contract.address.to_string();
+    exec(&mut deps, &contract, ExecuteMsg::IncreaseAllowance {
+        spender: POOL.to_string(),
+        amount: Uint128::new(1_000),
+        expires: None,
+    }).unwrap();
+
+    let res = exec(&mut deps, POOL, ExecuteMsg::TransferFrom {
+        owner: contract,
+        recipient: "carol".to_string(),
+        amount: Uint128::new(1_000),
+    }).unwrap();
+    assert!(!liquifies(&res));
+}
+
+#[test]
+fn zero_amount_transfers_are_rejected() {
+    let mut deps = setup_liquify();
+    let zero = Uint128::zero();
+    let msgs = vec![
+        ExecuteMsg::Transfer { recipient: "carol".to_string(), amount: zero },
+        ExecuteMsg::TransferFrom { owner: "alice".to_string(), recipient: "carol".to_string(), amount: zero },
+        ExecuteMsg::Send { contract: "receiver".to_string(), amount: zero, msg: Binary::default() },
+        ExecuteMsg::SendFrom { owner: "alice".to_string(), contract: "receiver".to_string(), amount: zero, msg: Binary::default() },
+    ];
+    for msg in msgs {
+        assert_eq!(exec(&mut deps, "alice", msg).unwrap_err(), ContractError::InvalidZeroAmount {});
+    }
+}
+
+#[test]
+fn reply_adds_liquidity_from_measured_balance() {
+    let mut deps = setup_liquify();
+    let contract = mock_env().contract.address;
+    CONFIG.update(deps.as_mut().storage, |mut cfg| -> StdResult<_> {
+        cfg.marketing_address = Some(Addr::unchecked("treasury"));
+        cfg.marketing_tokens = Uint128::new(1_000);
+        Ok(cfg)
+    }).unwrap();
+    deps.querier.update_balance(contract.clone(), coins(500, DENOM));
+
+    // sells the 1k marketing tokens and half of the other 4k
+    let res = transfer(&mut deps, "alice", "carol", 1_000);
+    // quoted 299 juno, less 1% of slippage
+    assert_eq!(liquify_swap(&res), Some((Uint128::new(3_000), Uint128::new(296))));
+    assert_eq!(res.messages.iter().find(|msg| msg.id == LIQUIFY_SWAP_REPLY_ID).map(|msg| msg.reply_on.clone()), Some(ReplyOn::Success));
+
+    // the pool pays out less than the 299 its reserves promised
+    deps.querier.update_balance(contract, coins(500 + 290, DENOM));
+    let res = swap_reply(&mut deps);
+
+    // 1k of the 3k sold was the treasury's
+    assert_eq!(bank_sends(&res), vec![("treasury".to_string(), Uint128::new(96))]);
+
+    // the other 194 juno are paired with 1941 of the 2k tokens left
+    let add_liquidity = res.messages.iter().find_map(|msg| match &msg.msg {
+        CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, funds }) if contract_addr == POOL => Some((from_json(msg).unwrap(), funds.clone())),
+        _ => None,
+    });
+    assert_eq!(add_liquidity, Some((
+        WasmswapExecuteMsg::AddLiquidity {
+            token1_amount: Uint128::new(194),
+            // 194 of the 1M juno in the pool mint 194 of its 1M LP, less 1%
+            min_liquidity: Uint128::new(192),
+            max_token2: Uint128::new(1_941),
+            expiration: None,
+        },
+        coins(194, DENOM),
+    )));
+}
+
+#[test]
+fn liquify_slippage_is_capped_and_swaps_expire() {
+    let mut msg = instantiate_msg();
+    msg.max_slippage_bps = 10_001;
+    let err = instantiate(mock_dependencies().as_mut(), mock_env(), mock_info(OWNER, &[]), msg).unwrap_err();
+    assert_eq!(err, ContractError::SlippageTooHigh {});
+
+    let mut deps = setup_liquify();
+    let err = exec(&mut deps, OWNER, ExecuteMsg::SetLiquifySlippage { max_slippage_bps: 10_001, swap_expiration: None }).unwrap_err();
+    assert_eq!(err, ContractError::SlippageTooHigh {});
+
+    exec(&mut deps, OWNER, ExecuteMsg::SetLiquifySlippage { max_slippage_bps: 0, swap_expiration: Some(Duration::Time(300)) }).unwrap();
+    let res = transfer(&mut deps, "alice", "carol", 1_000);
+    let swap = res.messages.iter().find_map(|msg| match &msg.msg {
+        CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, .. }) if contract_addr == POOL => {
+            match from_json(msg).unwrap() {
+                WasmswapExecuteMsg::Swap { min_output, expiration, .. } => Some((min_output, expiration)),
+                _ => None,
+            }
+        }
+        _ => None,
+    });
+    // the full 249 juno quoted, due within 300 seconds
+    assert_eq!(swap, Some((Uint128::new(249), Some(Expiration::AtTime(mock_env().block.time.plus_seconds(300))))));
+}
+
+#[test]
+fn auto_liquify_sells_the_threshold() {
+    let mut deps = setup_liquify();
+    let contract = mock_env().contract.address.to_string();
+    transfer(&mut deps, OWNER, &contract, 45_000);
+
+    // half of the 5k threshold, not of the 50k held
+    let res = transfer(&mut deps, "alice", "carol", 1_000);
+    assert_eq!(liquify_swap(&res), Some((Uint128::new(2_500), Uint128::new(246))));
+}
+
+#[test]
+fn contract_is_exempt_from_max_tx() {
+    let mut deps = setup();
+    mock_pool(&mut deps);
+    let contract = mock_env().contract.address.to_string();
+    transfer(&mut deps, OWNER, "alice", 200_000);
+    transfer(&mut deps, OWNER, &contract, 200_000);
+
+    let amount = Uint128::new(150_000);
+    let err = exec(&mut deps, "alice", ExecuteMsg::Transfer { recipient: "carol".to_string(), amount }).unwrap_err();
+    assert_eq!(err, ContractError::MaxTxAmountExceed {});
+    exec(&mut deps, &contract, ExecuteMsg::Transfer { recipient: POOL.to_string(), amount }).unwrap();
+}
+
+#[test]
+fn liquify_cap_cannot_go_below_threshold() {
+    let mut deps = setup();
+
+    let err = exec(&mut deps, OWNER, ExecuteMsg::SetMaxLiquifyAmount { amount: Uint128::new(4_999) }).unwrap_err();
+    assert_eq!(err, ContractError::LiquifyCapBelowThreshold {});
+    let err = exec(&mut deps, OWNER, ExecuteMsg::SetNumTokensSellToAddToLiquidity { amount: Uint128::new(100_001) }).unwrap_err();
+    assert_eq!(err, ContractError::LiquifyCapBelowThreshold {});
+
+    exec(&mut deps, OWNER, ExecuteMsg::SetMaxLiquifyAmount { amount: Uint128::new(5_000) }).unwrap();
+    exec(&mut deps, OWNER, ExecuteMsg::SetNumTokensSellToAddToLiquidity { amount: Uint128::new(5_000) }).unwrap();
+}
+
+#[test]
+fn liquify_after_renounce_and_cooldown() {
+    let mut deps = setup_liquify();
+    let liquify = ExecuteMsg::Liquify { amount: None };
+
+    let err = exec(&mut deps, "alice", liquify.clone()).unwrap_err();
+    assert_eq!(err, ContractError::Unauthorized {});
+
+    exec(&mut deps, OWNER, ExecuteMsg::RenounceOwnership {}).unwrap();
+    let res = exec(&mut deps, "alice", liquify.clone()).unwrap();
+    assert!(liquifies(&res));
+
+    let err = exec(&mut deps, "bob", liquify.clone()).unwrap_err();
+    assert!(matches!(err, ContractError::LiquifyCooldown { .. }));
+
+    // the interval is an hour
+    let res = exec_after(&mut deps, 3_600, "bob", liquify).unwrap();
+    assert!(liquifies(&res));
+}
+
+#[test]
+fn keeper_liquify_keeps_unpaid_marketing_fees() {
+    let mut deps = setup_liquify();
+    exec(&mut deps, OWNER, ExecuteMsg::GrantRole { address: "keeper".to_string(), role: Role::Keeper }).unwrap();
+    CONFIG.update(deps.as_mut().storage, |mut cfg| -> StdResult<_> {
+        cfg.marketing_tokens = Uint128::new(1_000);
+        Ok(cfg)
+    }).unwrap();
+
+    // no treasury yet, so only the other 4k of the 5k held are sold
+    let res = exec(&mut deps, "keeper", ExecuteMsg::Liquify { amount: None }).unwrap();
+    assert_eq!(liquify_swap(&res).map(|(input, _)| input), Some(Uint128::new(2_000)));
+}
+
+#[test]
+fn pool_side_is_detected_when_the_pool_changes() {
+    let mut deps = setup_liquify();
+    exec(&mut deps, OWNER, ExecuteMsg::GrantRole { address: OWNER.to_string(), role: Role::Keeper }).unwrap();
+    CONFIG.update(deps.as_mut().storage, |mut cfg| -> StdResult<_> {
+        cfg.denom_side = None;
+        Ok(cfg)
+    }).unwrap();
+
+    // nothing is sold into a pool of unknown orientation
+    assert!(!liquifies(&transfer(&mut deps, "alice", "carol", 1_000)));
+    let err = exec(&mut deps, OWNER, ExecuteMsg::Liquify { amount: None }).unwrap_err();
+    assert_eq!(err, ContractError::InvalidPool { pool: POOL.to_string() });
+
+    let err = exec(&mut deps, OWNER, ExecuteMsg::UpdatePool { address: Addr::unchecked("foreign_pool"), dex: None }).unwrap_err();
+    assert_eq!(err, ContractError::InvalidPool { pool: "foreign_pool".to_string() });
+    exec(&mut deps, OWNER, ExecuteMsg::UpdatePool { address: Addr::unchecked("flipped_pool"), dex: None }).unwrap();
+    exec_after(&mut deps, 60, OWNER, ExecuteMsg::ExecutePendingChange { id: 1 }).unwrap();
+    assert_eq!(config(deps.as_ref()).denom_side, Some(TokenSelect::Token2));
+
+    // the token is token1 of the flipped pool
+    let res = transfer(&mut deps, "alice", "carol", 1_000);
+    let pool_msgs = |res: &Response| -> Vec<(WasmswapExecuteMsg, Vec<Coin>)> {
+        res.messages.iter().filter_map(|msg| match &msg.msg {
+            CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, funds }) if contract_addr == "flipped_pool" => Some((from_json(msg).unwrap(), funds.clone())),
+            _ => None,
+        }).collect()
+    };
+    assert_eq!(pool_msgs(&res), vec![(
+        WasmswapExecuteMsg::Swap {
+            input_token: TokenSelect::Token1,
+            input_amount: Uint128::new(2_500),
+            // quoted 249 juno, less 1% of slippage
+            min_output: Uint128::new(246),
+            expiration: None,
+        },
+        vec![],
+    )]);
+
+    deps.querier.update_balance(mock_env().contract.address, coins(249, DENOM));
+    let res = swap_reply(&mut deps);
+    // 249 juno pair with 2480 of the 2.5k tokens left, and go in whole as
+    // max_token2 since the pool refunds what it does not pair
+    assert_eq!(pool_msgs(&res), vec![(
+        WasmswapExecuteMsg::AddLiquidity {
+            token1_amount: Uint128::new(2_480),
+            min_liquidity: Uint128::new(245),
+            max_token2: Uint128::new(249),
+            expiration: None,
+        },
+        coins(249, DENOM),
+    )]);
+}
+
+fn pair(buy_fee: Option<FeeProfile>, sell_fee: Option<FeeProfile>) -> AmmPair {
+    AmmPair { dex: Dex::Wasmswap, buy_fee, sell_fee }
+}
+
+fn amm_pairs(deps: Deps) -> Vec<AmmPairInfo> {
+    let res: AmmPairsResponse = from_json(query(deps, mock_env(), QueryMsg::AmmPairs {}).unwrap()).unwrap();
+    res.pairs
+}
+
+#[test]
+fn amm_pairs_are_added_and_removed_after_the_timelock() {
+    let mut deps = setup();
+    mock_pool(&mut deps);
+    let primary = AmmPairInfo { address: Addr::unchecked(POOL), pair: pair(None, None) };
+    assert_eq!(amm_pairs(deps.as_ref()), vec![primary.clone()]);
+
+    let err = exec(&mut deps, OWNER, ExecuteMsg::AddAmmPair {
+        address: "foreign_pool".to_string(), dex: Dex::Wasmswap, buy_fee: None, sell_fee: None,
+    }).unwrap_err();
+    assert_eq!(err, ContractError::InvalidPool { pool: "foreign_pool".to_string() });
+    let err = exec(&mut deps, OWNER, ExecuteMsg::AddAmmPair {
+        address: "other_pool".to_string(), dex: Dex::Wasmswap, buy_fee: None, sell_fee: Some(fee(2_000, 1_000, 0)),
+    }).unwrap_err();
+    assert_eq!(err, ContractError::FeeTooHigh {});
+
+    exec(&mut deps, OWNER, ExecuteMsg::AddAmmPair {
+        address: "other_pool".to_string(), dex: Dex::Wasmswap, buy_fee: None, sell_fee: Some(fee(100, 100, 0)),
+    }).unwrap();
+    assert_eq!(amm_pairs(deps.as_ref()), vec![primary.clone()]);
+    exec_after(&mut deps, 60, OWNER, ExecuteMsg::ExecutePendingChange { id: 1 }).unwrap();
+    let other = AmmPairInfo { address: Addr::unchecked("other_pool"), pair: pair(None, Some(fee(100, 100, 0))) };
+    assert_eq!(amm_pairs(deps.as_ref()), vec![other, primary.clone()]);
+
+    let err = exec(&mut deps, OWNER, ExecuteMsg::RemoveAmmPair { address: POOL.to_string() }).unwrap_err();
+    assert_eq!(err, ContractError::CannotRemovePrimaryPool {});
+    let err = exec(&mut deps, OWNER, ExecuteMsg::RemoveAmmPair { address: "flipped_pool".to_string() }).unwrap_err();
+    assert_eq!(err, ContractError::InvalidPool { pool: "flipped_pool".to_string() });
+
+    exec(&mut deps, OWNER, ExecuteMsg::RemoveAmmPair { address: "other_pool".to_string() }).unwrap();
+    exec_after(&mut deps, 60, OWNER, ExecuteMsg::ExecutePendingChange { id: 2 }).unwrap();
+    assert_eq!(amm_pairs(deps.as_ref()), vec![primary]);
+}
+
+#[test]
+fn primary_pool_cannot_be_removed_once_queued() {
+    let mut deps = setup();
+    mock_pool(&mut deps);
+    AMM_PAIRS.save(deps.as_mut().storage, Addr::unchecked("other_pool"), &pair(None, None)).unwrap();
+
+    // other_pool becomes primary while its removal waits
+    exec(&mut deps, OWNER, ExecuteMsg::RemoveAmmPair { address: "other_pool".to_string() }).unwrap();
+    exec(&mut deps, OWNER, ExecuteMsg::UpdatePool { address: Addr::unchecked("other_pool"), dex: None }).unwrap();
+    exec_after(&mut deps, 60, OWNER, ExecuteMsg::ExecutePendingChange { id: 2 }).unwrap();
+    let err = exec_after(&mut deps, 60, OWNER, ExecuteMsg::ExecutePendingChange { id: 1 }).unwrap_err();
+    assert_eq!(err, ContractError::CannotRemovePrimaryPool {});
+}
+
+#[test]
+fn pair_fees_replace_the_config_fees() {
+    let mut deps = setup();
+    AMM_PAIRS.save(deps.as_mut().storage, Addr::unchecked("other_pool"), &pair(None, Some(fee(100, 100, 0)))).unwrap();
+    transfer(&mut deps, OWNER, "alice", 100_000);
+    let contract = mock_env().contract.address.to_string();
+
+    // selling to other_pool pays its own 2%
+    transfer(&mut deps, "alice", "other_pool", 10_000);
+    assert_eq!(balance(deps.as_ref(), "other_pool"), Uint128::new(9_800));
+    assert_eq!(balance(deps.as_ref(), &contract), Uint128::new(100));
+
+    // buying from it falls back to the config's 10%
+    transfer(&mut deps, "other_pool", "carol", 5_000);
+    assert_eq!(balance(deps.as_ref(), "carol"), Uint128::new(4_500));
+    assert_eq!(balance(deps.as_ref(), &contract), Uint128::new(350));
+    assert_accounting(deps.as_ref());
+}
+
+/// LP token transfers in `res`, by recipient
+fn lp_transfers(res: &Response) -> Vec<(String, Uint128)> {
+    res.messages.iter().filter_map(|msg| match &msg.msg {
+        CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, .. }) if contract_addr == "lp" => match from_json(msg).unwrap() {
+            Cw20ExecuteMsg::Transfer { recipient, amount } => Some((recipient, amount)),
+            msg => panic!("unexpected LP message {:?}", msg),
+        },
+        _ => None,
+    }).collect()
+}
+
+fn set_lp_policy(deps: &mut MockDeps, seconds: u64, policy: LpPolicy) -> Result<Response, ContractError> {
+    let res = exec_after(deps, seconds, OWNER, ExecuteMsg::SetLpPolicy { policy })?;
+    let id = res.attributes.iter().find(|attr| attr.key == "change_id").unwrap().value.parse().unwrap();
+    exec_after(deps, seconds + 60, OWNER, ExecuteMsg::ExecutePendingChange { id })
+}
+
+#[test]
+fn lp_lock_can_only_be_extended_until_it_expires() {
+    let mut deps = setup_liquify();
+    let now = mock_env().block.time;
+    let lock = |seconds: u64| LpPolicy::Lock { until: Expiration::AtTime(now.plus_seconds(seconds)) };
+    set_lp_policy(&mut deps, 0, lock(1_000)).unwrap();
+
+    let withdraw_lp = |amount: u128| ExecuteMsg::WithdrawLp { amount: Uint128::new(amount), recipient: None };
+    let err = exec_after(&mut deps, 60, OWNER, withdraw_lp(1)).unwrap_err();
+    assert_eq!(err, ContractError::LpLocked { until: Expiration::AtTime(now.plus_seconds(1_000)) });
+
+    let until = Expiration::AtTime(now.plus_seconds(1_000));
+    for policy in [LpPolicy::Hold, LpPolicy::Burn { dead_address: Addr::unchecked("dead") }, lock(999)].iter() {
+        let err = set_lp_policy(&mut deps, 60, policy.clone()).unwrap_err();
+        assert_eq!(err, ContractError::LpLocked { until });
+    }
+    set_lp_policy(&mut deps, 60, lock(2_000)).unwrap();
+    assert_eq!(config(deps.as_ref()).lp_policy, lock(2_000));
+
+    // a change queued before the lock is extended cannot shorten it
+    exec_after(&mut deps, 200, OWNER, ExecuteMsg::SetLpPolicy { policy: lock(2_500) }).unwrap();
+    set_lp_policy(&mut deps, 200, lock(3_000)).unwrap();
+    let err = exec_after(&mut deps, 260, OWNER, ExecuteMsg::ExecutePendingChange { id: 3 }).unwrap_err();
+    assert_eq!(err, ContractError::LpLocked { until: Expiration::AtTime(now.plus_seconds(3_000)) });
+
+    let err = exec_after(&mut deps, 2_999, OWNER, withdraw_lp(1)).unwrap_err();
+    assert_eq!(err, ContractError::LpLocked { until: Expiration::AtTime(now.plus_seconds(3_000)) });
+    let res = exec_after(&mut deps, 3_000, OWNER, withdraw_lp(10_000)).unwrap();
+    assert_eq!(lp_transfers(&res), vec![(OWNER.to_string(), Uint128::new(10_000))]);
+
+    // expired, so any policy goes
+    set_lp_policy(&mut deps, 3_000, LpPolicy::Hold).unwrap();
+}
+
+#[test]
+fn locker_takes_held_and_minted_lp() {
+    let mut deps = setup_liquify();
+    let locker = LpPolicy::Locker { address: Addr::unchecked("locker") };
+
+    // the 1M held under the hold policy would be stranded otherwise
+    let res = set_lp_policy(&mut deps, 0, locker).unwrap();
+    assert_eq!(lp_transfers(&res), vec![("locker".to_string(), Uint128::new(1_000_000))]);
+    mock_pool_holding(&mut deps, 0);
+    let err = exec_after(&mut deps, 60, OWNER, ExecuteMsg::WithdrawLp { amount: Uint128::new(1), recipient: None }).unwrap_err();
+    assert_eq!(err, ContractError::LpNotWithdrawable { policy: "locker".to_string() });
+
+    transfer(&mut deps, "alice", "carol", 1_000);
+    deps.querier.update_balance(mock_env().contract.address, coins(249, DENOM));
+    let res = swap_reply(&mut deps);
+    let provide = res.messages.iter().find(|msg| msg.id == LIQUIFY_PROVIDE_REPLY_ID).unwrap();
+    assert!(matches!(&provide.msg, CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, .. }) if contract_addr == POOL));
+    assert_eq!(provide.reply_on, ReplyOn::Success);
+
+    // the pool minted 240 LP tokens
+    mock_pool_holding(&mut deps, 240);
+    let res = reply(deps.as_mut(), mock_env(), Reply {
+        id: LIQUIFY_PROVIDE_REPLY_ID,
+        result: SubMsgResult::Ok(SubMsgResponse { events: vec![], data: None }),
+    }).unwrap();
+    assert_eq!(lp_transfers(&res), vec![("locker".to_string(), Uint128::new(240))]);
+}
+
+#[test]
+fn held_lp_is_burned_when_switching_to_burn() {
+    let mut deps = setup_liquify();
+    let res = set_lp_policy(&mut deps, 0, LpPolicy::Burn { dead_address: Addr::unchecked("dead") }).unwrap();
+    assert_eq!(lp_transfers(&res), vec![("dead".to_string(), Uint128::new(1_000_000))]);
+
+    // nothing held and nothing swept without a checked pool
+    let mut deps = setup();
+    let res = set_lp_policy(&mut deps, 0, LpPolicy::Burn { dead_address: Addr::unchecked("dead") }).unwrap();
+    assert_eq!(lp_transfers(&res), vec![]);
+}
+
+fn withdrawals(deps: Deps, start_after: Option<u64>, limit: Option<u32>) -> Vec<Withdrawal> {
+    let res: WithdrawalsResponse = from_json(query(deps, mock_env(), QueryMsg::Withdrawals { start_after, limit }).unwrap()).unwrap();
+    res.withdrawals
+}
+
+#[test]
+fn withdrawals_are_capped_per_period() {
+    let mut deps = setup();
+    mock_pool(&mut deps);
+    let contract = mock_env().contract.address;
+    deps.querier.update_balance(contract, coins(1_000, DENOM));
+    let withdraw_native = |amount: u128| ExecuteMsg::WithdrawNative { amount: Uint128::new(amount), recipient: None };
+
+    exec(&mut deps, OWNER, withdraw_native(300)).unwrap();
+    exec(&mut deps, OWNER, ExecuteMsg::WithdrawLp { amount: Uint128::new(10_000), recipient: None }).unwrap();
+    // each asset has its own cap
+    let err = exec(&mut deps, OWNER, withdraw_native(201)).unwrap_err();
+    assert_eq!(err, ContractError::WithdrawCapExceeded { available: Uint128::new(200) });
+    let err = exec(&mut deps, OWNER, ExecuteMsg::WithdrawLp { amount: Uint128::new(1), recipient: None }).unwrap_err();
+    assert_eq!(err, ContractError::WithdrawCapExceeded { available: Uint128::zero() });
+
+    let mut env = mock_env();
+    env.block.time = env.block.time.plus_seconds(60 * 60);
+    execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), withdraw_native(200)).unwrap();
+
+    // a day later only the last withdrawal still counts
+    env.block.time = mock_env().block.time.plus_seconds(24 * 60 * 60);
+    execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), withdraw_native(300)).unwrap();
+    let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), withdraw_native(1)).unwrap_err();
+    assert_eq!(err, ContractError::WithdrawCapExceeded { available: Uint128::zero() });
+
+    // raising the caps waits for the timelock
+    let caps = WithdrawCaps {
+        period: Duration::Time(24 * 60 * 60),
+        lp_amount: Uint128::new(10_000),
+        quote_amount: Uint128::new(1_000),
+    };
+    execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::SetWithdrawCaps { caps }).unwrap();
+    let err = execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::ExecutePendingChange { id: 1 }).unwrap_err();
+    assert!(matches!(err, ContractError::Timelocked { .. }));
+    env.block.time = env.block.time.plus_seconds(60);
+    execute(deps.as_mut(), env.clone(), mock_info(OWNER, &[]), ExecuteMsg::ExecutePendingChange { id: 1 }).unwrap();
+    execute(deps.as_mut(), env, mock_info(OWNER, &[]), withdraw_native(500)).unwrap();
+}
+
+#[test]
+fn withdrawal_history_is_paginated() {
+    let mut deps = setup();
+    let contract = mock_env().contract.address;
+    deps.querier.update_balance(contract, coins(1_000, DENOM));
+    exec(&mut deps, OWNER, ExecuteMsg::GrantRole { address: "manager".to_string(), role: Role::LiquidityManager }).unwrap();
+
+    let mut env = mock_env();
+    for amount in 1..=12u128 {
+        env.block.height += 1;
+        let (sender, recipient) = if amount % 2 == 0 { ("manager", None) } else { (OWNER, Some("alice".to_string())) };
+        let msg = ExecuteMsg::WithdrawNative { amount: Uint128::new(amount), recipient };
+        execute(deps.as_mut(), env.clone(), mock_info(sender, &[]), msg).unwrap();
+    }
+
+    // the default page is 10, oldest first
+    let page = withdrawals(deps.as_ref(), None, None);
+    assert_eq!(page.iter().map(|withdrawal| withdrawal.id).collect::<Vec<_>>(), (1..=10).collect::<Vec<_>>());
+    assert_eq!(page[0], Withdrawal {
+        id: 1,
+        asset: Denom::Native(DENOM.to_string()),
+        amount: Uint128::new(1),
+        recipient: Addr::unchecked("alice"),
+        by: Addr::unchecked(OWNER),
+        height: mock_env().block.height + 1,
+        time: mock_env().block.time,
+    });
+    assert_eq!(page[1].recipient, Addr::unchecked(OWNER));
+    assert_eq!(page[1].by, Addr::unchecked("manager"));
+
+    let page = withdrawals(deps.as_ref(), Some(10), None);
+    assert_eq!(page.iter().map(|withdrawal| withdrawal.amount.u128()).collect::<Vec<_>>(), vec![11, 12]);
+    let page = withdrawals(deps.as_ref(), Some(3), Some(2));
+    assert_eq!(page.iter().map(|withdrawal| withdrawal.id).collect::<Vec<_>>(), vec![4, 5]);
+    assert!(withdrawals(deps.as_ref(), Some(12), None).is_empty());
+    assert_eq!(withdrawals(deps.as_ref(), None, Some(100)).len(), 12);
+}
@@ -0,0 +1,46 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use cosmwasm_std::Uint128;
+use cw20::{Denom, Expiration};
+
+pub use wasmswap::msg::{QueryMsg, TokenSelect};
+
+/// The wasmswap execute messages sent by this contract.
+/// `wasmswap::msg::ExecuteMsg` is built on an older cw20, whose `Expiration`
+/// cannot be created from here. The JSON is the same.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ExecuteMsg {
+    AddLiquidity {
+        token1_amount: Uint128,
+        min_liquidity: Uint128,
+        max_token2: Uint128,
+        expiration: Option<Expiration>,
+    },
+    Swap {
+        input_token: TokenSelect,
+        input_amount: Uint128,
+        min_output: Uint128,
+        expiration: Option<Expiration>,
+    },
+}
+
+/// `wasmswap::msg::InfoResponse`, with the `Denom` of the cw20 used here
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct InfoResponse {
+    pub token1_reserve: Uint128,
+    pub token1_denom: Denom,
+    pub token2_reserve: Uint128,
+    pub token2_denom: Denom,
+    pub lp_token_supply: Uint128,
+    pub lp_token_address: String,
+}
+
+/// The other side of the pool
+pub fn opposite(side: &TokenSelect) -> TokenSelect {
+    match side {
+        TokenSelect::Token1 => TokenSelect::Token2,
+        TokenSelect::Token2 => TokenSelect::Token1,
+    }
+}